@@ -1,47 +1,222 @@
-#[derive(Debug, Clone)]
+use crate::secret::{SecretBytes, SecretString};
+use crypto::keys::bip39::wordlist;
+use iota_client::api::GetAddressesBuilder;
+use iota_client::bee_message::address::{Address, Ed25519Address};
+use log::*;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The maximum edit distance for a word in the wordlist to be suggested for an unknown word.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+#[derive(Debug)]
+pub enum MnemonicError {
+    WordCount(usize),
+    UnknownWord {
+        position: usize,
+        word: String,
+        suggestions: Vec<&'static str>,
+    },
+    ChecksumMismatch,
+    Other(wordlist::Error),
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WordCount(n) => write!(f, "expected 12, 15, 18, 21 or 24 words, got {}", n),
+            Self::UnknownWord {
+                position,
+                word,
+                suggestions,
+            } => {
+                write!(f, "word {} \"{}\" is not in the wordlist", position, word)?;
+
+                if suggestions.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, "; did you mean {}?", suggestions.join(", "))
+                }
+            }
+            Self::ChecksumMismatch => write!(
+                f,
+                "the checksum is wrong; some words may be mistyped, swapped or missing"
+            ),
+            Self::Other(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+/// Edit distance between two words.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diag
+            } else {
+                1 + diag.min(above).min(row[j])
+            };
+            diag = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Validate a mnemonic against the English wordlist, returning it normalized (lowercase, single
+/// spaces) on success.
+pub fn validate_mnemonic(mnemonic: &str) -> Result<SecretString, MnemonicError> {
+    let words: Zeroizing<Vec<String>> = Zeroizing::new(
+        mnemonic
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect(),
+    );
+
+    if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+        return Err(MnemonicError::WordCount(words.len()));
+    }
+
+    let english = wordlist::ENGLISH.words();
+
+    for (i, word) in words.iter().enumerate() {
+        if english.contains(&word.as_str()) {
+            continue;
+        }
+
+        // Words in the list are unique by their first 4 letters, so a matching prefix is the
+        // strongest hint; otherwise fall back to the closest words by edit distance.
+        let prefix = word.get(..4);
+        let mut candidates: Vec<(usize, &'static str)> = english
+            .iter()
+            .filter_map(|candidate| {
+                if prefix.map_or(false, |prefix| candidate.starts_with(prefix)) {
+                    Some((0, *candidate))
+                } else {
+                    let distance = levenshtein(word, candidate);
+                    (distance <= MAX_SUGGESTION_DISTANCE).then(|| (distance, *candidate))
+                }
+            })
+            .collect();
+        candidates.sort_unstable();
+
+        return Err(MnemonicError::UnknownWord {
+            position: i + 1,
+            word: word.clone(),
+            suggestions: candidates.into_iter().take(3).map(|(_, c)| c).collect(),
+        });
+    }
+
+    let mnemonic = SecretString::from(words.join(" "));
+
+    match wordlist::verify(mnemonic.expose(), &wordlist::ENGLISH) {
+        Ok(()) => Ok(mnemonic),
+        Err(wordlist::Error::ChecksumMismatch) => Err(MnemonicError::ChecksumMismatch),
+        Err(e) => Err(MnemonicError::Other(e)),
+    }
+}
+
+#[derive(Debug)]
 pub struct ChrysalisAccount {
-    seed: [u8; 64],
-    mnemonic: String,
+    seed: SecretBytes<64>,
+    mnemonic: SecretString,
 }
 
 impl ChrysalisAccount {
-    pub fn new() -> Self {
-        let mut entropy = [0u8; 32];
-        crypto::utils::rand::fill(&mut entropy).unwrap();
+    pub fn new(passphrase: &str) -> Self {
+        let mut entropy = SecretBytes::<32>::zeroed();
+        crypto::utils::rand::fill(entropy.expose_mut()).unwrap();
 
-        let mnemonic = crypto::keys::bip39::wordlist::encode(
-            &entropy,
-            &crypto::keys::bip39::wordlist::ENGLISH,
-        )
-        .unwrap();
+        let mnemonic =
+            SecretString::from(wordlist::encode(entropy.expose(), &wordlist::ENGLISH).unwrap());
 
-        let mut seed = [0u8; 64];
-        crypto::keys::bip39::mnemonic_to_seed(&mnemonic, "", &mut seed);
+        let mut seed = SecretBytes::zeroed();
+        crypto::keys::bip39::mnemonic_to_seed(mnemonic.expose(), passphrase, seed.expose_mut());
 
         Self { seed, mnemonic }
     }
 
-    pub fn from_mnemonic(mnemonic: &str) -> Result<Self, crypto::keys::bip39::wordlist::Error> {
-        crypto::keys::bip39::wordlist::verify(
-            mnemonic.trim(),
-            &crypto::keys::bip39::wordlist::ENGLISH,
-        )
-        .unwrap();
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<Self, MnemonicError> {
+        let mnemonic = validate_mnemonic(mnemonic)?;
 
-        let mut seed = [0u8; 64];
-        crypto::keys::bip39::mnemonic_to_seed(mnemonic.trim(), "", &mut seed);
+        let mut seed = SecretBytes::zeroed();
+        crypto::keys::bip39::mnemonic_to_seed(mnemonic.expose(), passphrase, seed.expose_mut());
 
-        Ok(Self {
-            seed,
-            mnemonic: mnemonic.to_string(),
-        })
+        Ok(Self { seed, mnemonic })
     }
 
     pub fn seed(&self) -> &[u8] {
-        &self.seed
+        self.seed.expose()
     }
 
     pub fn mnemonic(&self) -> &str {
-        &self.mnemonic
+        self.mnemonic.expose()
+    }
+
+    /// Write the mnemonic to a new file readable by the owner only. The file is written under a
+    /// temporary name first, then hard-linked into place, so that [path] either doesn't exist or
+    /// contains the whole mnemonic; an existing file at [path] is never overwritten.
+    pub fn save_mnemonic<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+
+        let mut tmp_name = file_name.to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&tmp_path)?;
+        let result = file
+            .write_all(self.mnemonic.expose().as_bytes())
+            .and_then(|_| file.write_all(b"\n"))
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::hard_link(&tmp_path, path));
+
+        // The result of writing the mnemonic matters more than the cleanup, but a leftover copy
+        // of the mnemonic must not go unnoticed either
+        if let Err(e) = fs::remove_file(&tmp_path) {
+            warn!(
+                "failed to remove the temporary mnemonic file, remove it by hand: {}: {}",
+                e,
+                tmp_path.display()
+            );
+        }
+
+        result
+    }
+
+    /// Generate the Ed25519 address at the given account and address index.
+    pub async fn address(
+        &self,
+        account_index: usize,
+        address_index: usize,
+    ) -> Result<Ed25519Address, iota_client::Error> {
+        let generated_addrs = GetAddressesBuilder::new(&iota_client::Seed::from_bytes(self.seed()))
+            .with_account_index(account_index)
+            .with_range(address_index..address_index + 1)
+            .get_all_raw()
+            .await?;
+
+        let Address::Ed25519(address) = generated_addrs[0].0;
+
+        Ok(address)
     }
 }
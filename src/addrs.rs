@@ -1,5 +1,7 @@
 use iota_legacy::ternary::tryte::TryteBuf;
 use log::*;
+use std::collections::HashMap;
+use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
@@ -35,7 +37,7 @@ impl FromStr for AddrInfo {
                 return Err("failed to parse the second column into an index number");
             }
 
-            if idx_usize.is_err() {
+            if bal_usize.is_err() {
                 return Err("failed to parse the third column into a balance amount");
             }
 
@@ -54,20 +56,23 @@ impl FromStr for AddrInfo {
     }
 }
 
+impl fmt::Display for AddrInfo {
+    /// Format as a line of the addresses file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.addr, self.idx, self.bal)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Addrs {
     inner: Vec<AddrInfo>,
 }
 
-// impl Addrs {
-//     pub fn new() -> Self {
-//         Self { inner: Vec::new() }
-//     }
-
-//     pub fn from_inner(inner: Vec<AddrInfo>) -> Self {
-//         Self { inner }
-//     }
-// }
+impl Addrs {
+    pub fn from_inner(inner: Vec<AddrInfo>) -> Self {
+        Self { inner }
+    }
+}
 
 impl Deref for Addrs {
     type Target = Vec<AddrInfo>;
@@ -87,17 +92,63 @@ impl FromStr for Addrs {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut inner = Vec::new();
+        let mut inner: Vec<AddrInfo> = Vec::new();
+        let mut conflicts = 0;
+
+        // Where each address and index is in [inner], for files of many exports
+        let mut by_addr: HashMap<String, usize> = HashMap::new();
+        let mut by_idx: HashMap<usize, usize> = HashMap::new();
 
         for (i, l) in s.lines().enumerate() {
-            match l.parse() {
-                Ok(info) => inner.push(info),
+            let info: AddrInfo = match l.parse() {
+                Ok(info) => info,
                 Err(err) => {
                     // Silently ignore any unrecognized line
                     info!("silently ignoring line {}: {}", i + 1, err);
                     continue;
                 }
             };
+
+            // The same address listed twice (e.g. from two exports) must not be spent twice
+            if let Some(known) = by_addr.get(&info.addr).map(|&pos| &inner[pos]) {
+                if known.idx == info.idx && known.bal == info.bal {
+                    debug!("line {}: dropping duplicate address {}", i + 1, info.addr);
+                } else {
+                    error!(
+                        "line {}: address {} conflicts with an earlier entry: index {} balance {}, \
+                        was index {} balance {}",
+                        i + 1,
+                        info.addr,
+                        info.idx,
+                        info.bal,
+                        known.idx,
+                        known.bal
+                    );
+                    conflicts += 1;
+                }
+
+                continue;
+            }
+
+            // This is fine if the addresses belong to different seeds, so it's only a warning;
+            // addresses that don't belong to a seed are rejected when matching anyway
+            if let Some(known) = by_idx.get(&info.idx).map(|&pos| &inner[pos]) {
+                warn!(
+                    "line {}: index {} is listed with address {}, but also with address {}",
+                    i + 1,
+                    info.idx,
+                    info.addr,
+                    known.addr
+                );
+            }
+
+            by_addr.insert(info.addr.clone(), inner.len());
+            by_idx.entry(info.idx).or_insert(inner.len());
+            inner.push(info);
+        }
+
+        if conflicts > 0 {
+            return Err("conflicting address entries are found");
         }
 
         Ok(Self { inner })
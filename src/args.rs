@@ -1,4 +1,10 @@
-use clap::{crate_description, crate_name, crate_version, App, AppSettings, Arg};
+use crate::config::Layers;
+use crate::network::Network;
+use crate::pow;
+use crate::secret::SecretString;
+use clap::{
+    crate_description, crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand,
+};
 use std::process;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,183 +25,523 @@ impl ParallelMode {
     }
 }
 
+/// Where the proof of work of migration bundles is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PowMode {
+    Local,
+    Node,
+    Remote(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Migrate(Args),
+    Inspect(InspectArgs),
+    Convert(ConvertArgs),
+    EncryptSeeds(EncryptSeedsArgs),
+}
+
+impl Command {
+    pub fn from_cli() -> Self {
+        let matches = App::new(crate_name!())
+            .version(crate_version!())
+            .about(crate_description!())
+            .args(&Args::args())
+            .subcommand(
+                SubCommand::with_name("inspect")
+                    .about("Decode and verify migration bundle trytes offline")
+                    .args(&InspectArgs::args()),
+            )
+            .subcommand(
+                SubCommand::with_name("convert")
+                    .about("Convert between a Chrysalis address and its legacy migration address")
+                    .args(&ConvertArgs::args()),
+            )
+            .subcommand(
+                SubCommand::with_name("encrypt-seeds")
+                    .about("Encrypt a plaintext seeds file with a password")
+                    .args(&EncryptSeedsArgs::args()),
+            )
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .setting(AppSettings::SubcommandsNegateReqs)
+            .setting(AppSettings::ColoredHelp)
+            .get_matches();
+
+        match matches.subcommand() {
+            ("inspect", Some(matches)) => Self::Inspect(InspectArgs::from_matches(matches)),
+            ("convert", Some(matches)) => Self::Convert(ConvertArgs::from_matches(matches)),
+            ("encrypt-seeds", Some(matches)) => {
+                Self::EncryptSeeds(EncryptSeedsArgs::from_matches(matches))
+            }
+            _ => Self::Migrate(Args::from_matches(&matches)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InspectArgs {
+    pub bundles: String,
+    pub hrp: String,
+}
+
+impl InspectArgs {
+    fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("bundles")
+                .index(1)
+                .required(true)
+                .help("Where to read the bundle trytes, or - for stdin"),
+            Arg::with_name("hrp")
+                .long("hrp")
+                .takes_value(true)
+                .help("Human-readable part of the decoded Chrysalis address"),
+        ]
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            bundles: matches.value_of("bundles").unwrap().to_owned(),
+            hrp: matches.value_of("hrp").unwrap_or("iota").to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvertArgs {
+    pub address: String,
+    pub hrp: String,
+}
+
+impl ConvertArgs {
+    fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("address")
+                .index(1)
+                .required(true)
+                .help("A bech32 Ed25519 address, or a 90-tryte legacy migration address"),
+            Arg::with_name("hrp")
+                .long("hrp")
+                .takes_value(true)
+                .help("Human-readable part of the converted Chrysalis address"),
+        ]
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            address: matches.value_of("address").unwrap().to_owned(),
+            hrp: matches.value_of("hrp").unwrap_or("iota").to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncryptSeedsArgs {
+    pub input: String,
+    pub output: String,
+}
+
+impl EncryptSeedsArgs {
+    fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("input")
+                .index(1)
+                .required(true)
+                .help("Where to read the plaintext seeds"),
+            Arg::with_name("output")
+                .index(2)
+                .required(true)
+                .help("Where to write the encrypted seeds"),
+        ]
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            input: matches.value_of("input").unwrap().to_owned(),
+            output: matches.value_of("output").unwrap().to_owned(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Args {
     pub legacy_node: String,
     pub chrysalis_node: String,
-    pub permanode: String,
-    pub mnemonic: Option<String>,
+    pub permanode: Option<String>,
+    pub mnemonic: Option<SecretString>,
+    pub target_bech32: Option<String>,
+    pub passphrase_file: Option<String>,
+    pub passphrase_prompt: bool,
+    pub mnemonic_out: Option<String>,
+    pub stronghold_snapshot: Option<String>,
     pub target_account: usize,
     pub target_address: usize,
-    pub seeds: String,
+    pub seeds: Option<String>,
+    pub seeds_prompt: bool,
     pub addresses: Option<String>,
+    pub orphans_out: Option<String>,
     pub security_level: u8,
+    pub network: Network,
+    pub confirm_network: Option<Network>,
     pub minimum_weight_magnitude: u8,
+    pub depth: u8,
     pub parallel_mode: ParallelMode,
+    pub max_parallel_seeds: usize,
+    pub threads: Option<usize>,
+    pub pow: PowMode,
+    pub pow_threads: usize,
+    pub max_requests: usize,
+    pub requests_per_second: f64,
     pub dry_run: bool,
     pub yes: bool,
+    pub unsafe_print_seeds: bool,
 }
 
 impl Args {
-    pub fn from_cli() -> Self {
-        let matches = App::new(crate_name!())
-            .version(crate_version!())
-            .about(crate_description!())
-            .arg(
-                Arg::with_name("legacy-node")
-                    .long("legacy-node")
-                    .takes_value(true)
-                    .help("Custom URL to a legacy node"),
-            )
-            .arg(
-                Arg::with_name("chrysalis-node")
-                    .long("chrysalis-node")
-                    .takes_value(true)
-                    .help("Custom URL to a Chrysalis node"),
-            )
-            .arg(
-                Arg::with_name("permanode")
-                    .long("permanode")
-                    .takes_value(true)
-                    .help("Custom URL to a Permanode"),
-            )
-            .arg(
-                Arg::with_name("mnemonic")
-                    .long("mnemonic")
-                    .takes_value(true)
-                    .help("Set a mnemonic of seed on Chrysalis to migrate to"),
-            )
-            .arg(
-                Arg::with_name("target-account")
-                    .long("target-account")
-                    .takes_value(true)
-                    .help("The account index to send migration bundles to"),
-            )
-            .arg(
-                Arg::with_name("target-address")
-                    .long("target-address")
-                    .takes_value(true)
-                    .help("The address index to send migration bundles to"),
-            )
-            .arg(
-                Arg::with_name("seeds")
-                    .long("seeds")
-                    .short("s")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Where to read the seeds"),
-            )
-            .arg(
-                Arg::with_name("addresses")
-                    .long("addresses")
-                    .short("a")
-                    .takes_value(true)
-                    .required(true)
-                    .help("Where to read the confirmed addresses"),
-            )
-            .arg(
-                Arg::with_name("security-level")
-                    .long("security-level")
-                    .short("l")
-                    .takes_value(true)
-                    .possible_values(&["1", "2", "3"])
-                    .help("Security level used in the legacy network"),
-            )
-            .arg(
-                Arg::with_name("minimum-weight-magnitude")
-                    .long("minimum-weight-magnitude")
-                    .takes_value(true)
-                    .help("Custom minimum weight of magnitude"),
-            )
-            .arg(
-                Arg::with_name("parallel-mode")
-                    .long("parallel-mode")
-                    .takes_value(true)
-                    .possible_values(&["seed", "search", "all", "none"])
-                    .help("Mode of parallel processing"),
-            )
-            .arg(
-                Arg::with_name("dry-run")
-                    .long("dry-run")
-                    .short("D")
-                    .takes_value(false)
-                    .help("Don't actually perform the migration"),
-            )
-            .arg(
-                Arg::with_name("yes")
-                    .long("yes")
-                    .short("y")
-                    .takes_value(false)
-                    .help("Gain JoJo power"),
-            )
-            .setting(AppSettings::ArgRequiredElseHelp)
-            .setting(AppSettings::ColoredHelp)
-            .get_matches();
+    fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .help("Where to read the config file (default: the standard location, if any)"),
+            Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .help("The profile in the config file to use (default: the default profile)"),
+            Arg::with_name("legacy-node")
+                .long("legacy-node")
+                .takes_value(true)
+                .help("Custom URL to a legacy node (default: from the network)"),
+            Arg::with_name("chrysalis-node")
+                .long("chrysalis-node")
+                .takes_value(true)
+                .help("Custom URL to a Chrysalis node (default: from the network)"),
+            Arg::with_name("permanode")
+                .long("permanode")
+                .takes_value(true)
+                .help("Custom URL to a Permanode (default: from the network)"),
+            Arg::with_name("mnemonic")
+                .long("mnemonic")
+                .takes_value(true)
+                .help("Set a mnemonic of seed on Chrysalis to migrate to"),
+            Arg::with_name("target-bech32")
+                .long("target-bech32")
+                .takes_value(true)
+                .conflicts_with("mnemonic")
+                .help("Set an Ed25519 bech32 address on Chrysalis to migrate to"),
+            Arg::with_name("passphrase-file")
+                .long("passphrase-file")
+                .takes_value(true)
+                .conflicts_with_all(&["target-bech32", "passphrase-prompt"])
+                .help("Where to read the BIP39 passphrase of the mnemonic"),
+            Arg::with_name("passphrase-prompt")
+                .long("passphrase-prompt")
+                .takes_value(false)
+                .conflicts_with("target-bech32")
+                .help("Prompt for the BIP39 passphrase of the mnemonic"),
+            Arg::with_name("mnemonic-out")
+                .long("mnemonic-out")
+                .takes_value(true)
+                .conflicts_with_all(&["mnemonic", "target-bech32"])
+                .help("Where to write the mnemonic of the new account on Chrysalis"),
+            Arg::with_name("stronghold-snapshot")
+                .long("stronghold-snapshot")
+                .takes_value(true)
+                .conflicts_with("target-bech32")
+                .help("Where to export the account on Chrysalis as a Stronghold snapshot"),
+            Arg::with_name("target-account")
+                .long("target-account")
+                .takes_value(true)
+                .help("The account index to send migration bundles to"),
+            Arg::with_name("target-address")
+                .long("target-address")
+                .takes_value(true)
+                .help("The address index to send migration bundles to"),
+            Arg::with_name("seeds")
+                .long("seeds")
+                .short("s")
+                .takes_value(true)
+                .required_unless("seeds-prompt")
+                .help("Where to read the seeds, either in plaintext or encrypted, or - for stdin"),
+            Arg::with_name("seeds-prompt")
+                .long("seeds-prompt")
+                .takes_value(false)
+                .conflicts_with("seeds")
+                .help("Prompt for the seeds one at a time instead"),
+            Arg::with_name("addresses")
+                .long("addresses")
+                .short("a")
+                .takes_value(true)
+                .required(true)
+                .help("Where to read the confirmed addresses"),
+            Arg::with_name("orphans-out")
+                .long("orphans-out")
+                .takes_value(true)
+                .help("Where to write the addresses that match no seed"),
+            Arg::with_name("security-level")
+                .long("security-level")
+                .short("l")
+                .takes_value(true)
+                .possible_values(&["1", "2", "3"])
+                .help("Security level used in the legacy network"),
+            Arg::with_name("network")
+                .long("network")
+                .takes_value(true)
+                .possible_values(&["mainnet", "testnet", "custom"])
+                .help("The network to migrate on, which sets the nodes (default: testnet)"),
+            Arg::with_name("confirm-network")
+                .long("confirm-network")
+                .takes_value(true)
+                .possible_values(&["mainnet", "testnet", "custom"])
+                .help("Confirm the network to migrate on ahead, which mainnet requires with --yes"),
+            Arg::with_name("minimum-weight-magnitude")
+                .long("minimum-weight-magnitude")
+                .takes_value(true)
+                .help("Custom minimum weight of magnitude (default: from the network)"),
+            Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .help("Custom depth of tip selection (default: from the network)"),
+            Arg::with_name("parallel-mode")
+                .long("parallel-mode")
+                .takes_value(true)
+                .possible_values(&["seed", "search", "all", "none"])
+                .help("Mode of parallel processing"),
+            Arg::with_name("max-parallel-seeds")
+                .long("max-parallel-seeds")
+                .takes_value(true)
+                .help("How many seeds to process at once in the seed or all parallel mode"),
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .help("How many threads to use for address generation (default: all cores)"),
+            Arg::with_name("pow")
+                .long("pow")
+                .takes_value(true)
+                .possible_values(&["local", "node", "remote"])
+                .help("Where to do the proof of work: locally, on the legacy node, or remotely"),
+            Arg::with_name("pow-url")
+                .long("pow-url")
+                .takes_value(true)
+                .help("URL to the remote proof of work service (with --pow remote)"),
+            Arg::with_name("pow-threads")
+                .long("pow-threads")
+                .takes_value(true)
+                .help(
+                    "How many threads each proof of work can use, up to 243 (default: all cores)",
+                ),
+            Arg::with_name("max-requests")
+                .long("max-requests")
+                .takes_value(true)
+                .help("How many operations can be sent to the legacy node at once"),
+            Arg::with_name("requests-per-second")
+                .long("requests-per-second")
+                .takes_value(true)
+                .help("How many operations can be sent to the legacy node per second"),
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .short("D")
+                .takes_value(false)
+                .help("Don't actually perform the migration"),
+            Arg::with_name("yes")
+                .long("yes")
+                .short("y")
+                .takes_value(false)
+                .help("Gain JoJo power"),
+            Arg::with_name("unsafe-print-seeds")
+                .long("unsafe-print-seeds")
+                .takes_value(false)
+                .help("Print full seeds in logs and reports instead of fingerprints (UNSAFE)"),
+        ]
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Self {
+        // Options not on the command line are taken from the environment and the config file
+        let values = Layers::load(matches).unwrap_or_else(|e| {
+            eprintln!("Error: failed to load the config file: {}", e);
+            process::exit(1);
+        });
+
+        // Values from the environment and the config file aren't checked by clap
+        let parse_network = |x: &str| match x {
+            "mainnet" => Network::Mainnet,
+            "testnet" => Network::Testnet,
+            "custom" => Network::Custom,
+            _ => {
+                eprintln!("Error: invalid network: {}", x);
+                process::exit(1);
+            }
+        };
+        let network = values
+            .value_of("network")
+            .map_or(Network::Testnet, parse_network);
+
+        // The custom network has no default nodes
+        let node = |name: &str, default: Option<&str>| {
+            values
+                .value_of(name)
+                .or(default)
+                .map(|x| x.to_owned())
+                .unwrap_or_else(|| {
+                    eprintln!("Error: --{} is required on the {} network", name, network);
+                    process::exit(1);
+                })
+        };
 
         Self {
-            legacy_node: matches
-                .value_of("legacy-node")
-                .unwrap_or(crate::LEGACY_TESTNET_NODE_URL)
-                .to_owned(),
-            chrysalis_node: matches
-                .value_of("chrysalis-node")
-                .unwrap_or(crate::CHRYSALIS_TESTNET_NODE_URL)
-                .to_owned(),
-            permanode: matches
+            legacy_node: node("legacy-node", network.legacy_node()),
+            chrysalis_node: node("chrysalis-node", network.chrysalis_node()),
+            permanode: values
                 .value_of("permanode")
-                .unwrap_or(crate::PERMANODE_URL)
-                .to_owned(),
-            mnemonic: match matches.value_of("mnemonic") {
-                Some(mnemonic) => {
-                    crypto::keys::bip39::wordlist::verify(
-                        mnemonic.trim(),
-                        &crypto::keys::bip39::wordlist::ENGLISH,
-                    )
-                    .unwrap();
-                    Some(mnemonic.trim().to_owned())
-                }
-                None => None,
-            },
-            target_account: match matches.value_of("target-account") {
+                .or_else(|| network.permanode())
+                .map(|x| x.to_owned()),
+            // The mnemonic is validated when the account is created
+            mnemonic: values
+                .value_of("mnemonic")
+                .map(|x| SecretString::new(x.trim())),
+            target_bech32: values
+                .value_of("target-bech32")
+                .map(|x| x.trim().to_owned()),
+            passphrase_file: values.value_of("passphrase-file").map(|x| x.to_owned()),
+            passphrase_prompt: values.is_present("passphrase-prompt"),
+            mnemonic_out: values.value_of("mnemonic-out").map(|x| x.to_owned()),
+            stronghold_snapshot: values.value_of("stronghold-snapshot").map(|x| x.to_owned()),
+            target_account: match values.value_of("target-account") {
                 Some(x) => x.parse().unwrap_or_else(|e| {
                     eprintln!("Error: invalid target account index: {}: {}", e, x);
                     process::exit(1);
                 }),
                 None => 0, // default
             },
-            target_address: match matches.value_of("target-address") {
+            target_address: match values.value_of("target-address") {
                 Some(x) => x.parse().unwrap_or_else(|e| {
                     eprintln!("Error: invalid target address index: {}: {}", e, x);
                     process::exit(1);
                 }),
                 None => 0, // default
             },
-            seeds: matches.value_of("seeds").unwrap().to_owned(),
-            addresses: matches.value_of("addresses").map(|x| x.to_owned()),
-            security_level: match matches.value_of("security-level") {
-                Some(x) => x.parse().unwrap_or_else(|e| {
-                    eprintln!("Error: invalid security level: {}: {}", e, x);
+            seeds: values.value_of("seeds").map(|x| x.to_owned()),
+            seeds_prompt: values.is_present("seeds-prompt"),
+            addresses: values.value_of("addresses").map(|x| x.to_owned()),
+            orphans_out: values.value_of("orphans-out").map(|x| x.to_owned()),
+            security_level: match values.value_of("security-level") {
+                Some(x) => match x.parse() {
+                    Ok(n) if (1..=3).contains(&n) => n,
+                    _ => {
+                        eprintln!("Error: invalid security level: {}", x);
+                        process::exit(1);
+                    }
+                },
+                None => 2, // default
+            },
+            network,
+            confirm_network: values.value_of("confirm-network").map(parse_network),
+            minimum_weight_magnitude: match values.value_of("minimum-weight-magnitude") {
+                Some(x) => match x.parse() {
+                    Ok(n) if n > 0 && n <= 243 => n,
+                    _ => {
+                        eprintln!("Error: invalid minimum weight of magnitude: {}", x);
+                        process::exit(1);
+                    }
+                },
+                None => network.minimum_weight_magnitude().unwrap_or_else(|| {
+                    eprintln!(
+                        "Error: --minimum-weight-magnitude is required on the {} network",
+                        network
+                    );
                     process::exit(1);
                 }),
-                None => 2, // default
             },
-            minimum_weight_magnitude: match matches.value_of("security-level") {
+            depth: match values.value_of("depth") {
                 Some(x) => x.parse().unwrap_or_else(|e| {
-                    eprintln!("Error: invalid minimum weight of magnitude: {}: {}", e, x);
+                    eprintln!("Error: invalid depth: {}: {}", e, x);
+                    process::exit(1);
+                }),
+                None => network.depth().unwrap_or_else(|| {
+                    eprintln!("Error: --depth is required on the {} network", network);
                     process::exit(1);
                 }),
-                None => 14, // default
             },
-            parallel_mode: match matches.value_of("parallel-mode") {
+            parallel_mode: match values.value_of("parallel-mode") {
                 Some("seed") => ParallelMode::ParallelSeeds,
                 Some("search") => ParallelMode::ParallelSearch,
                 Some("all") => ParallelMode::ParallelAll,
                 Some("none") => ParallelMode::NoParallel,
-                Some(_) => unreachable!(), // clap won't allow any other
+                Some(x) => {
+                    eprintln!("Error: invalid parallel mode: {}", x);
+                    process::exit(1);
+                }
                 None => ParallelMode::NoParallel,
             },
-            dry_run: matches.is_present("dry-run"),
-            yes: matches.is_present("yes"),
+            max_parallel_seeds: match values.value_of("max-parallel-seeds") {
+                Some(x) => match x.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        eprintln!("Error: invalid number of parallel seeds: {}", x);
+                        process::exit(1);
+                    }
+                },
+                None => 16, // default
+            },
+            threads: values.value_of("threads").map(|x| match x.parse() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    eprintln!("Error: invalid number of threads: {}", x);
+                    process::exit(1);
+                }
+            }),
+            pow: match values.value_of("pow") {
+                Some("local") | None => PowMode::Local, // default
+                Some("node") => PowMode::Node,
+                Some("remote") => match values.value_of("pow-url") {
+                    Some(url) => PowMode::Remote(url.to_owned()),
+                    None => {
+                        eprintln!("Error: --pow-url is required with --pow remote");
+                        process::exit(1);
+                    }
+                },
+                Some(x) => {
+                    eprintln!("Error: invalid proof of work mode: {}", x);
+                    process::exit(1);
+                }
+            },
+            pow_threads: match values.value_of("pow-threads") {
+                Some(x) => match x.parse() {
+                    Ok(n) if n > 0 && n <= pow::MAX_THREADS => n,
+                    _ => {
+                        eprintln!(
+                            "Error: invalid number of PoW threads (at most {}): {}",
+                            pow::MAX_THREADS,
+                            x
+                        );
+                        process::exit(1);
+                    }
+                },
+                None => std::thread::available_parallelism()
+                    .map_or(1, |n| n.get().min(pow::MAX_THREADS)), // default
+            },
+            max_requests: match values.value_of("max-requests") {
+                Some(x) => match x.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        eprintln!("Error: invalid number of requests: {}", x);
+                        process::exit(1);
+                    }
+                },
+                None => 8, // default
+            },
+            requests_per_second: match values.value_of("requests-per-second") {
+                Some(x) => match x.parse::<f64>() {
+                    Ok(n) if n > 0.0 && n.is_finite() => n,
+                    _ => {
+                        eprintln!("Error: invalid number of requests per second: {}", x);
+                        process::exit(1);
+                    }
+                },
+                None => 10.0, // default
+            },
+            dry_run: values.is_present("dry-run"),
+            yes: values.is_present("yes"),
+            unsafe_print_seeds: values.is_present("unsafe-print-seeds"),
         }
     }
 }
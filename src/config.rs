@@ -0,0 +1,306 @@
+use clap::ArgMatches;
+use log::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// The options that can also be set by environment variables and profiles. Everything else (e.g.
+/// the seeds, the mnemonic and the confirmations) must be given on the command line.
+const LAYERED: &[&str] = &[
+    "legacy-node",
+    "chrysalis-node",
+    "permanode",
+    "network",
+    "minimum-weight-magnitude",
+    "depth",
+    "security-level",
+    "parallel-mode",
+    "max-parallel-seeds",
+    "threads",
+    "pow",
+    "pow-url",
+    "pow-threads",
+    "max-requests",
+    "requests-per-second",
+    "target-account",
+    "target-address",
+];
+
+/// The options whose values only make sense on one network. They are only taken from environment
+/// variables or a profile that also set the network to migrate on, so that e.g. the mainnet nodes of
+/// a profile are never used when another network is selected on the command line.
+const NETWORK_DEPENDENT: &[&str] = &[
+    "legacy-node",
+    "chrysalis-node",
+    "permanode",
+    "minimum-weight-magnitude",
+    "depth",
+    "pow",
+    "pow-url",
+];
+
+/// Environment variables are named after the options with this prefix, e.g.
+/// IOTA_MIGRATION_LEGACY_NODE for --legacy-node.
+const ENV_PREFIX: &str = "IOTA_MIGRATION_";
+
+/// The config file, e.g.:
+///
+/// ```toml
+/// default-profile = "testnet-rehearsal"
+///
+/// [profiles.testnet-rehearsal]
+/// network = "testnet"
+/// parallel-mode = "all"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, HashMap<String, toml::Value>>,
+}
+
+/// The name of the environment variable of the option [name].
+fn env_name(name: &str) -> String {
+    format!("{}{}", ENV_PREFIX, name.to_uppercase().replace('-', "_"))
+}
+
+/// The config file in the standard location, e.g. ~/.config/iota-migration-cli/config.toml.
+fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("iota-migration-cli").join("config.toml"))
+}
+
+/// Read the option values of the profile [name] (or the default profile, if any) from the config
+/// file at [path].
+fn load_profile(path: &str, name: Option<&str>) -> Result<HashMap<String, String>, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", e, path))?;
+    let profile = parse_profile(&data, name).map_err(|e| format!("{}: {}", e, path))?;
+
+    debug!(
+        "using {} options from the config file {}",
+        profile.len(),
+        path
+    );
+
+    Ok(profile)
+}
+
+/// Parse the option values of the profile [name] (or the default profile, if any) from the config
+/// file [data].
+fn parse_profile(data: &str, name: Option<&str>) -> Result<HashMap<String, String>, String> {
+    let mut config: Config = toml::from_str(data).map_err(|e| e.to_string())?;
+
+    let name = match name.map(|x| x.to_owned()).or(config.default_profile) {
+        Some(name) => name,
+        None => {
+            debug!("no profile is selected from the config file");
+            return Ok(HashMap::new());
+        }
+    };
+    let profile = config
+        .profiles
+        .remove(&name)
+        .ok_or_else(|| format!("no such profile: {}", name))?;
+
+    debug!("using profile {}", name);
+
+    profile
+        .into_iter()
+        .map(|(key, value)| {
+            if !LAYERED.contains(&key.as_str()) {
+                return Err(format!("option not allowed in profiles: {}: {}", key, name));
+            }
+
+            let value = match value {
+                toml::Value::String(x) => x,
+                toml::Value::Integer(x) => x.to_string(),
+                toml::Value::Float(x) => x.to_string(),
+                _ => return Err(format!("invalid value of option: {}: {}", key, name)),
+            };
+
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// The values of options, taken from the first of these that has it: the command line, the
+/// environment variables, and the selected profile of the config file. Built-in defaults are up to
+/// the caller.
+pub struct Layers<'a> {
+    matches: &'a ArgMatches<'a>,
+    env: HashMap<String, String>,
+    profile: HashMap<String, String>,
+}
+
+impl<'a> Layers<'a> {
+    /// Load the environment variables and the config file on top of [matches].
+    pub fn load(matches: &'a ArgMatches<'a>) -> Result<Self, String> {
+        let env: HashMap<String, String> = LAYERED
+            .iter()
+            .filter_map(|&name| env::var(env_name(name)).ok().map(|x| (name.to_owned(), x)))
+            .collect();
+
+        let from_cli_or_env = |name: &str| {
+            matches
+                .value_of(name)
+                .map(|x| x.to_owned())
+                .or_else(|| env::var(env_name(name)).ok())
+        };
+        let profile_name = from_cli_or_env("profile");
+
+        // An explicitly given config file must exist, while the standard one is optional
+        let path = match from_cli_or_env("config") {
+            Some(path) => Some(path),
+            None => default_path()
+                .filter(|path| path.is_file())
+                .map(|path| path.to_string_lossy().into_owned()),
+        };
+
+        let profile = match path {
+            Some(ref path) => load_profile(path, profile_name.as_deref())?,
+            None if profile_name.is_some() => return Err("no config file is found".to_owned()),
+            None => HashMap::new(),
+        };
+
+        Ok(Self::new(matches, env, profile))
+    }
+
+    /// Layer [env] and [profile] on top of [matches], dropping the options that depend on the
+    /// network from the layers that don't select the network to migrate on.
+    fn new(
+        matches: &'a ArgMatches<'a>,
+        mut env: HashMap<String, String>,
+        mut profile: HashMap<String, String>,
+    ) -> Self {
+        let network = matches
+            .value_of("network")
+            .or_else(|| env.get("network").map(|x| x.as_str()))
+            .or_else(|| profile.get("network").map(|x| x.as_str()))
+            .map(|x| x.to_owned());
+
+        for (layer, values) in [("environment", &mut env), ("profile", &mut profile)] {
+            if values.get("network") == network.as_ref() && network.is_some() {
+                continue;
+            }
+
+            for name in NETWORK_DEPENDENT {
+                if values.remove(*name).is_some() {
+                    warn!(
+                        "ignoring {} from the {}, which doesn't select the {} network",
+                        name,
+                        layer,
+                        network.as_deref().unwrap_or("default")
+                    );
+                }
+            }
+        }
+
+        Self {
+            matches,
+            env,
+            profile,
+        }
+    }
+
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.matches
+            .value_of(name)
+            .or_else(|| self.env.get(name).map(|x| x.as_str()))
+            .or_else(|| self.profile.get(name).map(|x| x.as_str()))
+    }
+
+    pub fn is_present(&self, name: &str) -> bool {
+        self.matches.is_present(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{App, Arg};
+
+    fn matches(args: &[&str]) -> ArgMatches<'static> {
+        App::new("test")
+            .args(
+                &LAYERED
+                    .iter()
+                    .map(|name| Arg::with_name(name).long(name).takes_value(true))
+                    .collect::<Vec<_>>(),
+            )
+            .get_matches_from(std::iter::once("test").chain(args.iter().copied()))
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn command_line_over_env_over_profile() {
+        let matches = matches(&["--threads", "1"]);
+        let env = values(&[("threads", "2"), ("max-requests", "2")]);
+        let profile = values(&[("threads", "3"), ("max-requests", "3"), ("depth", "3")]);
+        let layers = Layers::new(&matches, env, profile);
+
+        assert_eq!(layers.value_of("threads"), Some("1"));
+        assert_eq!(layers.value_of("max-requests"), Some("2"));
+        assert_eq!(layers.value_of("security-level"), None);
+    }
+
+    #[test]
+    fn network_dependent_options_follow_the_network() {
+        let profile = values(&[("network", "mainnet"), ("legacy-node", "https://mainnet")]);
+
+        let matches_testnet = matches(&["--network", "testnet"]);
+        let layers = Layers::new(&matches_testnet, HashMap::new(), profile.clone());
+        assert_eq!(layers.value_of("network"), Some("testnet"));
+        assert_eq!(layers.value_of("legacy-node"), None);
+
+        let matches_none = matches(&[]);
+        let layers = Layers::new(&matches_none, HashMap::new(), profile);
+        assert_eq!(layers.value_of("network"), Some("mainnet"));
+        assert_eq!(layers.value_of("legacy-node"), Some("https://mainnet"));
+
+        let env = values(&[("legacy-node", "https://elsewhere")]);
+        let layers = Layers::new(&matches_none, env, HashMap::new());
+        assert_eq!(layers.value_of("legacy-node"), None);
+    }
+
+    #[test]
+    fn profiles_are_selected_by_name_or_default() {
+        let data = r#"
+            default-profile = "rehearsal"
+
+            [profiles.rehearsal]
+            network = "testnet"
+            depth = 3
+
+            [profiles.production]
+            network = "mainnet"
+        "#;
+
+        let profile = parse_profile(data, None).unwrap();
+        assert_eq!(profile.get("depth").map(|x| x.as_str()), Some("3"));
+
+        let profile = parse_profile(data, Some("production")).unwrap();
+        assert_eq!(profile.get("network").map(|x| x.as_str()), Some("mainnet"));
+
+        assert!(parse_profile(data, Some("nonexistent")).is_err());
+    }
+
+    #[test]
+    fn invalid_profiles_are_rejected() {
+        // Not an option
+        assert!(parse_profile("[profiles.a]\nnonexistent = 1", Some("a")).is_err());
+        // Only taken from the command line
+        assert!(parse_profile("[profiles.a]\nmnemonic = \"x\"", Some("a")).is_err());
+        assert!(parse_profile("[profiles.a]\nyes = true", Some("a")).is_err());
+        // Not a value
+        assert!(parse_profile("[profiles.a]\nthreads = [1]", Some("a")).is_err());
+        // Not a field of the config file
+        assert!(parse_profile("nonexistent = 1", None).is_err());
+    }
+}
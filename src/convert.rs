@@ -0,0 +1,53 @@
+use crate::args::ConvertArgs;
+use crate::migaddr;
+use iota_legacy::client::migration;
+use log::*;
+
+pub fn convert(args: ConvertArgs) -> Result<(), ()> {
+    let address = args.address.trim();
+
+    // Legacy addresses are made of uppercase trytes only, which bech32 addresses never are
+    let (hrp, ed25519) = if address.chars().all(|c| c == '9' || c.is_ascii_uppercase()) {
+        debug!("converting from a legacy migration address");
+
+        if address.len() != 90 {
+            error!("a legacy migration address must be 90 trytes long, including the checksum");
+            return Err(());
+        }
+
+        match migaddr::decode(address) {
+            Ok(ed25519) => (args.hrp.clone(), ed25519),
+            Err(err) => {
+                error!("invalid legacy migration address: {}", err);
+                return Err(());
+            }
+        }
+    } else {
+        debug!("converting from a bech32 address");
+
+        match migaddr::parse_bech32(address) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                error!("invalid Chrysalis address: {}", err);
+                return Err(());
+            }
+        }
+    };
+
+    let legacy = match migaddr::encode(ed25519).and_then(|addr| migaddr::add_checksum(&addr)) {
+        Ok(legacy) => legacy,
+        Err(err) => {
+            error!("failed to encode the legacy migration address: {}", err);
+            return Err(());
+        }
+    };
+
+    println!(
+        "{} (legacy ternary address)\n\
+         {} (Chrysalis address)",
+        legacy,
+        migration::Address::Ed25519(ed25519).to_bech32(&hrp)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use crate::args::EncryptSeedsArgs;
+use crate::seeds::Seeds;
+use age::secrecy::Secret;
+use log::*;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use zeroize::Zeroizing;
+
+/// Every age-encrypted file starts with this header.
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+
+/// Whether [data] is an age-encrypted file.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(AGE_MAGIC)
+}
+
+/// Decrypt a passphrase-encrypted age file in memory.
+pub fn decrypt(data: &[u8], password: &str) -> Result<Zeroizing<String>, String> {
+    let decryptor = match age::Decryptor::new(data).map_err(|e| e.to_string())? {
+        age::Decryptor::Passphrase(decryptor) => decryptor,
+        _ => return Err("the file is not encrypted with a password".to_owned()),
+    };
+
+    let mut plaintext = Zeroizing::new(String::new());
+    decryptor
+        .decrypt(&Secret::new(password.to_owned()), None)
+        .map_err(|e| e.to_string())?
+        .read_to_string(&mut plaintext)
+        .map_err(|e| e.to_string())?;
+
+    Ok(plaintext)
+}
+
+/// Encrypt [plaintext] into an age file with a passphrase.
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>, String> {
+    let encryptor = age::Encryptor::with_user_passphrase(Secret::new(password.to_owned()));
+
+    let mut ciphertext = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut ciphertext)
+        .map_err(|e| e.to_string())?;
+    writer.write_all(plaintext).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+
+    Ok(ciphertext)
+}
+
+pub fn encrypt_seeds(args: EncryptSeedsArgs) -> Result<(), ()> {
+    let plaintext = match fs::read_to_string(&args.input) {
+        Ok(s) => Zeroizing::new(s),
+        Err(e) => {
+            error!("cannot read seeds from file: {}: {}", e, args.input);
+            return Err(());
+        }
+    };
+
+    // Parse it once to make sure that there are seeds to encrypt
+    match plaintext.parse::<Seeds>() {
+        Ok(seeds) if !seeds.is_empty() => info!("loaded {} seeds", seeds.len()),
+        Ok(_) => {
+            error!("no seed is found in {}", args.input);
+            return Err(());
+        }
+        Err(e) => {
+            error!("failed to parse the seeds file: {}", e);
+            return Err(());
+        }
+    }
+
+    let password = crate::prompt_secret("Password", true);
+
+    let ciphertext = match encrypt(plaintext.as_bytes(), password.expose()) {
+        Ok(ciphertext) => ciphertext,
+        Err(e) => {
+            error!("failed to encrypt the seeds: {}", e);
+            return Err(());
+        }
+    };
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    if let Err(e) = options
+        .open(&args.output)
+        .and_then(|mut file| file.write_all(&ciphertext).and_then(|_| file.sync_all()))
+    {
+        error!(
+            "cannot write encrypted seeds to file: {}: {}",
+            e, args.output
+        );
+        return Err(());
+    }
+
+    eprintln!(
+        "Encrypted seeds are written to {}. Remember to remove the plaintext file {} securely.",
+        args.output, args.input
+    );
+
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use crate::args::InspectArgs;
+use crate::migaddr;
+use iota_legacy::client::migration;
+#[allow(deprecated)]
+use iota_legacy::crypto::hashes::ternary::kerl::Kerl;
+use iota_legacy::crypto::hashes::ternary::Sponge;
+#[allow(deprecated)]
+use iota_legacy::crypto::signatures::ternary::wots::{normalize, WotsSignature};
+use iota_legacy::crypto::signatures::ternary::{PublicKey, RecoverableSignature};
+use iota_legacy::ternary::{T1B1Buf, T3B1Buf, TritBuf, Trits, TryteBuf, T1B1};
+use iota_legacy::transaction::bundled::{BundledTransaction, BundledTransactionField};
+use log::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+
+/// Length of a serialized transaction in trytes.
+const TRANSACTION_TRYTE_LEN: usize = 2673;
+
+/// The signature message fragment comes first in a serialized transaction.
+const PAYLOAD_TRIT_LEN: usize = 6561;
+
+/// Address, value, obsolete tag, timestamp, current index and last index - the part of a
+/// transaction that is hashed into the bundle hash.
+const ESSENCE_TRIT_LEN: usize = 486;
+
+struct Transaction {
+    trits: TritBuf<T1B1Buf>,
+    inner: BundledTransaction,
+}
+
+fn trytes(trits: &Trits<T1B1>) -> String {
+    trits
+        .encode::<T3B1Buf>()
+        .iter_trytes()
+        .map(char::from)
+        .collect()
+}
+
+fn parse_transactions(s: &str) -> Vec<Transaction> {
+    let mut transactions = Vec::new();
+
+    for (i, l) in s.lines().enumerate() {
+        // Accept the "Bundle trytes" list in the migration report as is
+        let l = l.trim().trim_start_matches("- ");
+
+        if l.len() != TRANSACTION_TRYTE_LEN {
+            // Silently ignore any unrecognized line
+            info!("silently ignoring line {}: not a transaction", i + 1);
+            continue;
+        }
+
+        let trits = match TryteBuf::try_from_str(l) {
+            Ok(trytes) => trytes.as_trits().encode::<T1B1Buf>(),
+            Err(err) => {
+                info!("silently ignoring line {}: {:?}", i + 1, err);
+                continue;
+            }
+        };
+
+        match BundledTransaction::from_trits(&trits) {
+            Ok(inner) => transactions.push(Transaction { trits, inner }),
+            Err(err) => {
+                warn!(
+                    "ignoring line {}: failed to decode transaction: {:?}",
+                    i + 1,
+                    err
+                );
+                continue;
+            }
+        }
+    }
+
+    transactions
+}
+
+#[allow(deprecated)]
+fn compute_bundle_hash(txs: &[Transaction]) -> Option<TritBuf<T1B1Buf>> {
+    let essence: TritBuf<T1B1Buf> = txs
+        .iter()
+        .flat_map(|tx| {
+            tx.trits
+                .subslice(PAYLOAD_TRIT_LEN..PAYLOAD_TRIT_LEN + ESSENCE_TRIT_LEN)
+                .iter()
+        })
+        .collect();
+
+    Kerl::default().digest(&essence).ok()
+}
+
+/// Verify the WOTS signature of the input at [pos], returning the security level (the number of
+/// signature fragments) on success.
+#[allow(deprecated)]
+fn verify_input(txs: &[Transaction], pos: usize, normalized_hash: &Trits<T1B1>) -> Option<usize> {
+    let address = txs[pos].inner.address();
+
+    // Signature fragments of an input span the input transaction and the following zero-value
+    // transactions to the same address.
+    let fragments: Vec<_> = txs[pos..]
+        .iter()
+        .enumerate()
+        .take_while(|(i, tx)| {
+            tx.inner.address() == address && (*i == 0 || *tx.inner.value().to_inner() == 0)
+        })
+        .map(|(_, tx)| tx.trits.subslice(0..PAYLOAD_TRIT_LEN))
+        .collect();
+    let security_level = fragments.len();
+    let signature_trits: TritBuf<T1B1Buf> = fragments
+        .into_iter()
+        .flat_map(|fragment| fragment.iter())
+        .collect();
+
+    let public_key = WotsSignature::<Kerl>::from_trits(signature_trits)
+        .ok()?
+        .recover_public_key(normalized_hash)
+        .ok()?;
+
+    if public_key.as_trits() == address.to_inner().as_slice() {
+        Some(security_level)
+    } else {
+        None
+    }
+}
+
+/// Print a decoded view of a single bundle, returning whether it is valid.
+#[allow(deprecated)]
+fn inspect_bundle(hash: &str, txs: &[Transaction], hrp: &str) -> bool {
+    let mut valid = true;
+    let mut report = String::new();
+
+    // The bundle must be complete to be verified
+    let last_index = *txs[0].inner.last_index().to_inner();
+    let complete = txs.len() == last_index + 1
+        && txs
+            .iter()
+            .enumerate()
+            .all(|(i, tx)| *tx.inner.index().to_inner() == i);
+
+    if !complete {
+        valid = false;
+        report.push_str(&format!(
+            "\nIncomplete: got {} of {} transactions",
+            txs.len(),
+            last_index + 1
+        ));
+    }
+
+    let computed_hash = if complete {
+        compute_bundle_hash(txs)
+    } else {
+        None
+    };
+    let hash_status = match computed_hash {
+        Some(ref computed) if trytes(computed) == hash => "valid".to_owned(),
+        Some(ref computed) => {
+            valid = false;
+            format!("MISMATCH, computed {}", trytes(computed))
+        }
+        None => {
+            valid = false;
+            "cannot be computed".to_owned()
+        }
+    };
+
+    let normalized_hash = computed_hash.and_then(|computed| normalize(&computed).ok());
+
+    let mut inputs = String::new();
+    let mut outputs = String::new();
+
+    for (pos, tx) in txs.iter().enumerate() {
+        let value = *tx.inner.value().to_inner();
+        let address = trytes(tx.inner.address().to_inner());
+        let address_checksum = migaddr::add_checksum(&address).unwrap_or_else(|_| address.clone());
+
+        if value < 0 {
+            let signature_status = match normalized_hash
+                .as_ref()
+                .and_then(|normalized| verify_input(txs, pos, normalized))
+            {
+                Some(security_level) => {
+                    format!("security level {}, signature valid", security_level)
+                }
+                None => {
+                    valid = false;
+                    "signature INVALID".to_owned()
+                }
+            };
+
+            inputs.push_str(&format!(
+                "\n- {} ({} i, {})",
+                address_checksum, value, signature_status
+            ));
+        } else if value > 0 {
+            let chrysalis_addr = match migaddr::decode(&address) {
+                Ok(ed25519) => format!(
+                    "{} (Chrysalis address)",
+                    migration::Address::Ed25519(ed25519).to_bech32(hrp)
+                ),
+                Err(err) => {
+                    valid = false;
+                    format!("not a migration address: {}", err)
+                }
+            };
+
+            outputs.push_str(&format!(
+                "\n- {} ({} i)\n  -> {}",
+                address_checksum, value, chrysalis_addr
+            ));
+        }
+    }
+
+    let sum: i64 = txs.iter().map(|tx| *tx.inner.value().to_inner()).sum();
+
+    if sum != 0 {
+        valid = false;
+        report.push_str(&format!("\nUnbalanced: values sum up to {} i", sum));
+    }
+
+    println!(
+        "=== Bundle Inspection ===\n\
+            Bundle hash: {} ({})\n\
+            Transactions: {}\n\
+            Inputs:{}\n\
+            Outputs:{}{}\n\
+            Result: {}\n\
+            =========================",
+        hash,
+        hash_status,
+        txs.len(),
+        inputs,
+        outputs,
+        report,
+        if valid { "VALID" } else { "INVALID" }
+    );
+
+    valid
+}
+
+pub fn inspect(args: InspectArgs) -> Result<(), ()> {
+    let s = if args.bundles == "-" {
+        let mut s = String::new();
+        io::stdin().read_to_string(&mut s).map(|_| s)
+    } else {
+        fs::read_to_string(&args.bundles)
+    };
+
+    let s = match s {
+        Ok(s) => s,
+        Err(e) => {
+            error!("cannot read bundle trytes: {}: {}", e, args.bundles);
+            return Err(());
+        }
+    };
+
+    let transactions = parse_transactions(&s);
+    info!("loaded {} transactions", transactions.len());
+
+    if transactions.is_empty() {
+        eprintln!("No transaction is loaded, nothing to do!");
+        return Err(());
+    }
+
+    // Group transactions by their bundle hashes, then sort them by their indices in bundles.
+    let mut bundles: BTreeMap<String, Vec<Transaction>> = BTreeMap::new();
+    for tx in transactions {
+        bundles
+            .entry(trytes(tx.inner.bundle().to_inner()))
+            .or_default()
+            .push(tx);
+    }
+
+    let mut all_valid = true;
+    for (hash, txs) in bundles.iter_mut() {
+        txs.sort_unstable_by_key(|tx| *tx.inner.index().to_inner());
+        txs.dedup_by_key(|tx| *tx.inner.index().to_inner());
+
+        all_valid &= inspect_bundle(hash, txs, &args.hrp);
+    }
+
+    if all_valid {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
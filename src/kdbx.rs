@@ -0,0 +1,94 @@
+use crate::seeds::{SeedInfo, Seeds};
+use keepass::{Database, NodeRef};
+use log::*;
+
+/// Every KeePass database starts with these two signatures (in little endian).
+const KDBX_MAGIC: &[u8] = &[0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+
+/// Whether [data] is a KeePass (KDBX) database, e.g. a Trinity SeedVault export.
+pub fn is_kdbx(data: &[u8]) -> bool {
+    data.starts_with(KDBX_MAGIC)
+}
+
+/// The custom field of an entry that holds the seed, in databases kept by hand where the password
+/// field holds something else (e.g. the PIN of the wallet).
+const SEED_FIELD: &str = "Seed";
+
+/// Load seeds from a KeePass (KDBX) database. Trinity SeedVault keeps a seed in the password of
+/// each entry, and the account name in the title, which is kept as the label of the seed. A custom
+/// "Seed" field takes precedence over the password.
+pub fn load_seeds(mut data: &[u8], password: &str) -> Result<Seeds, String> {
+    let db = Database::open(&mut data, Some(password), None).map_err(|e| e.to_string())?;
+
+    let mut inner = Vec::new();
+
+    for node in &db.root {
+        if let NodeRef::Entry(entry) = node {
+            let title = entry.get_title().unwrap_or_default();
+
+            let seed = entry
+                .get(SEED_FIELD)
+                .or_else(|| entry.get_password())
+                .unwrap_or_default();
+
+            match seed.parse::<SeedInfo>() {
+                Ok(mut info) => {
+                    if info.padded {
+                        warn!(
+                            "entry {}: seed {} is shorter than 81 trytes, padded with 9s",
+                            title,
+                            info.fingerprint()
+                        );
+                    }
+
+                    if !title.is_empty() {
+                        info.label = Some(title.to_owned());
+                    }
+
+                    inner.push(info);
+                }
+                Err(err) => {
+                    // Silently ignore any unrecognized entry
+                    info!("silently ignoring entry {}: {}", title, err);
+                    continue;
+                }
+            }
+        }
+    }
+
+    Ok(Seeds::from_inner(inner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A KDBX 3.1 database with the password "migration" and three entries: "Trinity" with a seed in
+    /// the password, "Custom" with a seed in the custom "Seed" field and a PIN in the password, and
+    /// "Email" with no seed at all.
+    const SEEDVAULT: &[u8] = include_bytes!("../tests/data/seedvault.kdbx");
+
+    #[test]
+    fn seeds_from_password_or_seed_field() {
+        assert!(is_kdbx(SEEDVAULT));
+
+        let seeds = load_seeds(SEEDVAULT, "migration").unwrap();
+        let seeds: Vec<_> = seeds
+            .iter()
+            .map(|info| (info.seed.expose().to_owned(), info.label.clone().unwrap()))
+            .collect();
+
+        assert_eq!(
+            seeds,
+            vec![
+                ("A".repeat(40) + &"B".repeat(41), "Trinity".to_owned()),
+                ("C".repeat(40) + &"D".repeat(41), "Custom".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_password() {
+        assert!(load_seeds(SEEDVAULT, "nonexistent").is_err());
+    }
+}
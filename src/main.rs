@@ -1,39 +1,114 @@
 mod account;
 mod addrs;
 mod args;
+mod config;
+mod convert;
+mod encryption;
+mod inspect;
+mod kdbx;
+mod matching;
+mod migaddr;
+mod network;
+mod pow;
+mod secret;
 mod seeds;
+#[cfg(feature = "stronghold")]
+mod stronghold;
 mod tasks;
+mod throttle;
 
 use account::ChrysalisAccount;
 use addrs::Addrs;
-use args::Args;
-use log::{debug, error, info, trace};
-use rayon::prelude::*;
-use seeds::Seeds;
-use std::{fs, io, process};
-
-const LEGACY_TESTNET_NODE_URL: &str = "https://nodes-legacy.iotatestmigration6.net";
-const CHRYSALIS_TESTNET_NODE_URL: &str = "https://api.lb-0.h.migration6.iotatestmigration6.net";
-const PERMANODE_URL: &str = "https://chronicle.iota.org/api";
+use args::{Command, PowMode};
+use iota_legacy::client::builder::ClientBuilder as LegacyClientBuilder;
+use iota_legacy::client::migration;
+use log::{debug, error, info, trace, warn};
+use network::Network;
+use pow::PowProvider;
+use secret::SecretString;
+use seeds::{SeedInfo, Seeds};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::{fs, path::Path, process};
+use throttle::Throttle;
+use tokio::sync::Semaphore;
+use zeroize::Zeroizing;
 
 fn main() {
     env_logger::init();
 
-    let args = Args::from_cli();
+    let args = match Command::from_cli() {
+        Command::Migrate(args) => args,
+        Command::Inspect(args) => {
+            trace!("{:?}", args);
+
+            if inspect::inspect(args).is_err() {
+                process::exit(1);
+            }
+
+            return;
+        }
+        Command::Convert(args) => {
+            trace!("{:?}", args);
+
+            if convert::convert(args).is_err() {
+                process::exit(1);
+            }
+
+            return;
+        }
+        Command::EncryptSeeds(args) => {
+            trace!("{:?}", args);
+
+            if encryption::encrypt_seeds(args).is_err() {
+                process::exit(1);
+            }
+
+            return;
+        }
+    };
     trace!("{:?}", args);
 
-    let seeds: Seeds = match fs::read_to_string(&args.seeds) {
-        Ok(s) => match s.parse() {
-            Ok(s) => s,
+    // The global thread pool is used to generate addresses
+    if let Some(threads) = args.threads {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            error!("failed to set up the thread pool: {}", e);
+            process::exit(1);
+        }
+    }
+
+    let seeds: Seeds = if let Some(ref seeds_file) = args.seeds {
+        // The confirmation is read from stdin as well, which is exhausted by then
+        if seeds_file == "-" && !args.yes {
+            error!("--yes is required when seeds are read from stdin");
+            process::exit(1);
+        }
+
+        let data = if seeds_file == "-" {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data).map(|_| data)
+        } else {
+            fs::read(seeds_file)
+        };
+
+        match data {
+            Ok(data) => match parse_seeds(Zeroizing::new(data)) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("failed to parse the seeds file: {}", e);
+                    process::exit(1);
+                }
+            },
             Err(e) => {
-                error!("failed to parse the seeds file: {}", e);
-                process::exit(1);
+                error!("cannot read seeds from file: {}: {}", e, seeds_file);
+                process::exit(e.raw_os_error().unwrap_or(2));
             }
-        },
-        Err(e) => {
-            error!("cannot read seeds from file: {}: {}", e, args.seeds);
-            process::exit(e.raw_os_error().unwrap_or(2));
         }
+    } else {
+        prompt_seeds()
     };
 
     let num_seeds = seeds.len();
@@ -68,6 +143,158 @@ fn main() {
         return;
     }
 
+    // Match the addresses against the seeds once, so that each seed only gets its own addresses
+    let seed_addrs: Vec<Addrs> = if let Some(ref addrs) = addrs {
+        info!(
+            "matching {} addresses against {} seeds",
+            num_addrs, num_seeds
+        );
+        let (seed_addrs, orphans) = matching::assign(&args, &seeds, addrs);
+
+        if !orphans.is_empty() {
+            warn!("{} addresses match no seed", orphans.len());
+            report_orphans(&orphans);
+
+            if let Some(ref orphans_out) = args.orphans_out {
+                if let Err(e) = save_orphans(&orphans, orphans_out) {
+                    error!(
+                        "cannot write orphaned addresses to file: {}: {}",
+                        e, orphans_out
+                    );
+                    process::exit(e.raw_os_error().unwrap_or(2));
+                }
+
+                println!(
+                    "Addresses matching no seed are written to {}.\n",
+                    orphans_out
+                );
+            }
+        }
+
+        seed_addrs
+    } else {
+        Vec::new()
+    };
+
+    // The BIP39 passphrase of the Chrysalis account, which is empty if not specified
+    let passphrase = if let Some(ref passphrase_file) = args.passphrase_file {
+        match fs::read_to_string(passphrase_file) {
+            Ok(s) => {
+                let s = Zeroizing::new(s);
+                SecretString::new(s.trim_end_matches(&['\r', '\n'][..]))
+            }
+            Err(e) => {
+                error!(
+                    "cannot read passphrase from file: {}: {}",
+                    e, passphrase_file
+                );
+                process::exit(e.raw_os_error().unwrap_or(2));
+            }
+        }
+    } else if args.passphrase_prompt {
+        // Only a new account needs the passphrase to be confirmed
+        prompt_secret("Passphrase", args.mnemonic.is_none())
+    } else {
+        SecretString::new("")
+    };
+
+    // Refuse to continue if the mnemonic or the Stronghold snapshot cannot be saved later
+    if args.stronghold_snapshot.is_some() && cfg!(not(feature = "stronghold")) {
+        error!("cannot export a Stronghold snapshot: built without the stronghold feature");
+        process::exit(1);
+    }
+
+    for out in [&args.mnemonic_out, &args.stronghold_snapshot]
+        .iter()
+        .copied()
+        .flatten()
+    {
+        if Path::new(out).exists() {
+            error!("refusing to overwrite an existing file: {}", out);
+            process::exit(1);
+        }
+    }
+
+    // Tokio asynchronous runtime to wait for asynchronous code in our synchronous code.
+    let async_rt = tokio::runtime::Runtime::new().unwrap();
+
+    // Make sure that the Chrysalis node is on the network we think we are migrating on, so that
+    // e.g. a testnet rehearsal pointed at a mainnet node doesn't go unnoticed
+    let node_hrp = match async_rt.block_on(query_bech32_hrp(&args.chrysalis_node)) {
+        Ok(node_hrp) => node_hrp,
+        Err(e) => {
+            error!("failed to query the network of the Chrysalis node: {}", e);
+            process::exit(1);
+        }
+    };
+
+    match args.network.hrp() {
+        Some(hrp) if hrp != node_hrp => {
+            error!(
+                "the Chrysalis node HRP {} does not match the HRP {} of the {} network",
+                node_hrp, hrp, args.network
+            );
+            process::exit(1);
+        }
+        _ => debug!("the Chrysalis node is on the network of HRP {}", node_hrp),
+    }
+
+    // Chrysalis addresses are shown with the HRP of the network they are on, which is the one of
+    // the network profile if it has one, or the one of the Chrysalis node otherwise
+    let hrp = node_hrp;
+
+    // Validate the provided target address against the Chrysalis network, if any
+    let target_bech32 = if let Some(ref bech32) = args.target_bech32 {
+        let (target_hrp, address) = match migaddr::parse_bech32(bech32) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                error!(
+                    "failed to use the provided target address: {}: {}",
+                    e, bech32
+                );
+                process::exit(1);
+            }
+        };
+
+        if target_hrp != hrp {
+            error!(
+                "the target address HRP {} does not match the Chrysalis node HRP {}",
+                target_hrp, hrp
+            );
+            process::exit(1);
+        }
+
+        Some(address)
+    } else {
+        None
+    };
+
+    // This instance from an older version of iota-client connects to the legacy network. It is
+    // shared by the migration tasks of all seeds.
+    let legacy_client = LegacyClientBuilder::new()
+        .node(&args.legacy_node)
+        .and_then(|builder| match args.permanode {
+            Some(ref permanode) => builder.permanode(permanode),
+            None => Ok(builder),
+        })
+        .and_then(|builder| builder.quorum(true).build())
+        .unwrap_or_else(|e| {
+            error!("failed to create the legacy client: {}", e);
+            process::exit(1);
+        });
+
+    // Requests to the legacy node from all seeds are limited together
+    let throttle = Arc::new(Throttle::new(args.max_requests, args.requests_per_second));
+
+    // Make sure that the legacy node is on the network to migrate on, and that the transactions to
+    // send will be accepted
+    if async_rt
+        .block_on(network::check(&args, &legacy_client, &throttle))
+        .is_err()
+    {
+        process::exit(1);
+    }
+
     // Prompt what is to be done
     if num_addrs == 0 {
         // println!(
@@ -79,12 +306,54 @@ fn main() {
     } else {
         println!(
             "Migrating from the given {} addresses for each of the {} seed(s).\n\
+             Network: {}\n\
              Legacy Node: {}\n\
              Chrysalis Node: {}",
-            num_addrs, num_seeds, args.legacy_node, args.chrysalis_node
+            num_addrs, num_seeds, args.network, args.legacy_node, args.chrysalis_node
         );
     }
 
+    if let Some(ref bech32) = args.target_bech32 {
+        println!("Target Address: {}", bech32);
+    }
+
+    // Migrating on the mainnet spends real funds, so it must be confirmed separately, and a network
+    // confirmed ahead must be the one to migrate on
+    match args.confirm_network {
+        Some(network) if network != args.network => {
+            error!(
+                "the network to migrate on is {}, but {} is confirmed",
+                args.network, network
+            );
+            process::exit(1);
+        }
+        Some(network) => {
+            eprintln!("Network {} - confirmed from command line", network);
+        }
+        None if args.network == Network::Mainnet => {
+            if args.yes {
+                error!("migrating on the mainnet with --yes requires --confirm-network mainnet");
+                process::exit(1);
+            }
+
+            eprint!("Funds on the mainnet will be migrated. Type \"mainnet\" to confirm: ");
+
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(_) if line.trim() == "mainnet" => debug!("user confirmed the mainnet"),
+                Ok(_) => {
+                    debug!("user input {} - exit", line.trim());
+                    return;
+                }
+                Err(e) => {
+                    debug!("{}", e);
+                    process::exit(255);
+                }
+            }
+        }
+        None => (),
+    }
+
     if args.yes {
         eprintln!("Continue? y - specified from command line");
     } else {
@@ -119,68 +388,368 @@ fn main() {
         }
     }
 
-    // Create a new account on Chrysalis - the target to migrate funds to,
-    // or use the provided mnemonic
-    let chrysalis_account = if let Some(ref mnemonic) = args.mnemonic {
-        debug!("using the provided mnemonic for an exiting account on Chrysalis");
-        let account = ChrysalisAccount::from_mnemonic(mnemonic);
+    // Use the provided target address, or create a new account on Chrysalis - the target to
+    // migrate funds to, or use the provided mnemonic
+    let chrysalis_addr = if let Some(address) = target_bech32 {
+        debug!("using the provided bech32 address on Chrysalis");
 
-        if let Err(e) = account {
-            error!("failed to use the provided mnemonic: {:?}", e);
-            process::exit(1);
-        }
-
-        account.unwrap()
+        address
     } else {
-        debug!("creating an account on Chrysalis");
-        let account = ChrysalisAccount::new();
+        let chrysalis_account = if let Some(ref mnemonic) = args.mnemonic {
+            debug!("using the provided mnemonic for an exiting account on Chrysalis");
+            let account = ChrysalisAccount::from_mnemonic(mnemonic.expose(), passphrase.expose());
 
-        println!(
-            "\n\
-             !!!!!!!!!!!!!!!!!!!!!!!!!!!!!\n\
-             !!! New Chrysalis Account !!!\n\
-             !!!   SAVE THE MNEMONIC!  !!!\n\
-             \n\
-             {}\n\
-             \n\
-             !!!   SAVE THE MNEMONIC!  !!!\n\
-             !!!!!!!!!!!!!!!!!!!!!!!!!!!!!\n\
-            ",
-            account.mnemonic(),
-        );
+            if let Err(e) = account {
+                error!("failed to use the provided mnemonic: {}", e);
+                process::exit(1);
+            }
 
-        account
-    };
+            account.unwrap()
+        } else {
+            debug!("creating an account on Chrysalis");
+            let account = ChrysalisAccount::new(passphrase.expose());
+
+            if let Some(ref mnemonic_out) = args.mnemonic_out {
+                if let Err(e) = save_mnemonic(&account, mnemonic_out, passphrase.expose()) {
+                    error!("failed to save the mnemonic: {}: {}", e, mnemonic_out);
+                    process::exit(1);
+                }
 
-    // This is the closure to run regardless of parallel or sequential
-    let migrate = |seed: &String| {
-        debug!("running migration task for seed {}", seed);
+                println!(
+                    "\nThe mnemonic of the new Chrysalis account is saved to {}\n",
+                    mnemonic_out
+                );
+            } else {
+                println!(
+                    "\n\
+                     !!!!!!!!!!!!!!!!!!!!!!!!!!!!!\n\
+                     !!! New Chrysalis Account !!!\n\
+                     !!!   SAVE THE MNEMONIC!  !!!\n\
+                     \n\
+                     {}\n\
+                     \n\
+                     !!!   SAVE THE MNEMONIC!  !!!\n\
+                     !!!!!!!!!!!!!!!!!!!!!!!!!!!!!\n\
+                    ",
+                    account.mnemonic(),
+                );
+            }
 
-        if num_addrs == 0 {
-            // No address is provided - generate and migrate
-            tasks::search_and_migrate(args.clone(), chrysalis_account.clone(), seed.clone())
-        } else {
-            // Addresses are provided - just migrate
-            tasks::collect_and_migrate(
-                args.clone(),
-                chrysalis_account.clone(),
-                seed.clone(),
-                addrs.as_ref().unwrap().clone(),
-            )
+            if !passphrase.is_empty() {
+                println!("!!! The account is protected by the passphrase. SAVE IT AS WELL !!!\n");
+            }
+
+            account
+        };
+
+        #[cfg(feature = "stronghold")]
+        if let Some(ref snapshot) = args.stronghold_snapshot {
+            let password = prompt_secret("Stronghold password", true);
+
+            if let Err(e) = async_rt.block_on(stronghold::export(
+                &chrysalis_account,
+                passphrase.expose(),
+                password.expose(),
+                Path::new(snapshot),
+            )) {
+                error!(
+                    "failed to export the Stronghold snapshot: {}: {}",
+                    e, snapshot
+                );
+                process::exit(1);
+            }
+
+            println!(
+                "The Chrysalis account is exported to the Stronghold snapshot {}\n",
+                snapshot
+            );
+        }
+
+        debug!("generating target Chrysalis address...");
+        match async_rt.block_on(chrysalis_account.address(args.target_account, args.target_address))
+        {
+            Ok(address) => {
+                println!(
+                    "Target Address: {}\n",
+                    migration::Address::Ed25519(address).to_bech32(&hrp)
+                );
+
+                address
+            }
+            Err(e) => {
+                error!("failed to generate the target address: {}", e);
+                process::exit(1);
+            }
         }
     };
 
-    let results: Vec<_> = if args.parallel_mode.is_parallel_seed() {
-        // Parallel seed processing - every seed will occupy a thread
-        debug!("processing each seed in parallel");
+    let pow = match args.pow {
+        PowMode::Local => PowProvider::Local(args.pow_threads),
+        PowMode::Node => PowProvider::Remote(legacy_client.clone()),
+        PowMode::Remote(ref url) => {
+            match LegacyClientBuilder::new()
+                .node(url)
+                .and_then(|builder| builder.build())
+            {
+                Ok(client) => PowProvider::Remote(client),
+                Err(e) => {
+                    error!(
+                        "failed to use the remote proof of work service: {}: {}",
+                        e, url
+                    );
+                    process::exit(1);
+                }
+            }
+        }
+    };
 
-        seeds.par_iter().map(migrate).collect()
+    // Every seed is migrated in an asynchronous task on the same runtime, but only so many tasks
+    // can be running at once
+    let max_parallel_seeds = if args.parallel_mode.is_parallel_seed() {
+        debug!(
+            "processing at most {} seeds in parallel",
+            args.max_parallel_seeds
+        );
+        args.max_parallel_seeds
     } else {
-        // Sequential seed processing
         debug!("processing each seed in sequence");
-
-        seeds.iter().map(migrate).collect()
+        1
     };
+    let semaphore = Arc::new(Semaphore::new(max_parallel_seeds));
+
+    let results: Vec<Result<(), ()>> = async_rt.block_on(async {
+        let handles: Vec<_> = seeds
+            .iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                let args = args.clone();
+                let hrp = hrp.clone();
+                let legacy_client = legacy_client.clone();
+                let throttle = throttle.clone();
+                let pow = pow.clone();
+                let seed = seed.clone();
+                let addrs = seed_addrs.get(i).cloned();
+                let semaphore = semaphore.clone();
+
+                tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await.unwrap();
+
+                    debug!(
+                        "running migration task for seed {}",
+                        seed.name(args.unsafe_print_seeds)
+                    );
+
+                    match addrs {
+                        // No address is provided - generate and migrate
+                        None => {
+                            tasks::search_and_migrate(
+                                args,
+                                legacy_client,
+                                throttle,
+                                pow,
+                                chrysalis_addr,
+                                hrp,
+                                seed,
+                            )
+                            .await
+                        }
+                        // Addresses are provided - just migrate
+                        Some(addrs) => {
+                            tasks::collect_and_migrate(
+                                args,
+                                legacy_client,
+                                throttle,
+                                pow,
+                                chrysalis_addr,
+                                hrp,
+                                seed,
+                                addrs,
+                            )
+                            .await
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            results.push(handle.await.unwrap_or_else(|e| {
+                error!("a migration task has failed: {}", e);
+                Err(())
+            }));
+        }
+
+        results
+    });
 
     debug!("{:?}", results);
+
+    println!("=== Summary ===");
+    for (seed, result) in seeds.iter().zip(&results) {
+        println!(
+            "- {}: {}",
+            seed.name(args.unsafe_print_seeds),
+            if result.is_ok() { "done" } else { "failed" }
+        );
+    }
+    println!("===============");
+}
+
+/// Parse the seeds file, which is either a KeePass database (e.g. a Trinity SeedVault export), an
+/// encrypted file, or a plaintext file. The former two are decrypted in memory.
+fn parse_seeds(data: Zeroizing<Vec<u8>>) -> Result<Seeds, String> {
+    if kdbx::is_kdbx(&data) {
+        debug!("loading seeds from a KeePass database");
+        let password = prompt_secret("Password", false);
+
+        kdbx::load_seeds(&data, password.expose())
+    } else if encryption::is_encrypted(&data) {
+        debug!("decrypting the seeds file");
+        let password = prompt_secret("Password", false);
+
+        encryption::decrypt(&data, password.expose())?
+            .parse()
+            .map_err(|e: &str| e.to_owned())
+    } else {
+        std::str::from_utf8(&data)
+            .map_err(|e| e.to_string())?
+            .parse()
+            .map_err(|e: &str| e.to_owned())
+    }
+}
+
+/// Prompt for seeds one at a time without echoing them. The checksum of each seed is shown, so
+/// that it can be compared with the one shown in Trinity.
+fn prompt_seeds() -> Seeds {
+    let mut inner = Vec::new();
+
+    eprintln!("Please input seeds one at a time, and an empty line to finish.");
+
+    loop {
+        let prompt = format!("Seed #{}: ", inner.len() + 1);
+        let seed = match rpassword::read_password_from_tty(Some(&prompt)) {
+            // Seeds are parsed as in files, where lowercase seeds are rejected
+            Ok(seed) => Zeroizing::new(seed),
+            Err(e) => {
+                debug!("{}", e);
+                process::exit(255);
+            }
+        };
+
+        if seed.trim().is_empty() {
+            break;
+        }
+
+        match seed.parse::<SeedInfo>() {
+            Ok(info) => {
+                if info.padded {
+                    eprintln!("The seed is shorter than 81 trytes, padded with 9s.");
+                }
+                eprintln!("Seed #{} checksum: {}", inner.len() + 1, info.checksum());
+                inner.push(info);
+            }
+            Err(e) => {
+                eprintln!("Invalid seed: {}, please try again.", e);
+                continue;
+            }
+        }
+    }
+
+    Seeds::from_inner(inner)
+}
+
+/// Save the mnemonic of a new account to [path], then read it back to make sure that the funds
+/// won't be migrated to an account that cannot be recovered.
+fn save_mnemonic(account: &ChrysalisAccount, path: &str, passphrase: &str) -> Result<(), String> {
+    account.save_mnemonic(path).map_err(|e| e.to_string())?;
+
+    let saved = Zeroizing::new(fs::read_to_string(path).map_err(|e| e.to_string())?);
+    let saved_account =
+        ChrysalisAccount::from_mnemonic(&saved, passphrase).map_err(|e| e.to_string())?;
+
+    if saved_account.seed() != account.seed() {
+        return Err("the saved mnemonic does not match".to_owned());
+    }
+
+    debug!("verified the mnemonic saved in {}", path);
+    Ok(())
+}
+
+/// Print the addresses that match no seed, so that the missing seeds can be looked for.
+fn report_orphans(orphans: &Addrs) {
+    let total: usize = orphans.iter().map(|addr| addr.bal).sum();
+
+    println!("=== Addresses Matching No Seed ===");
+    for addr in orphans.iter() {
+        println!("- {} (index {}): {} i", addr.addr, addr.idx, addr.bal);
+    }
+    println!("Total: {} addresses, {} i", orphans.len(), total);
+    println!("==================================\n");
+}
+
+/// Write the addresses that match no seed to [path] in the format of the addresses file, so that
+/// it can be fed back once the missing seeds are found.
+fn save_orphans(orphans: &Addrs, path: &str) -> io::Result<()> {
+    let total: usize = orphans.iter().map(|addr| addr.bal).sum();
+
+    let mut content = format!(
+        "# {} addresses matching no seed, {} i in total\n# address index balance\n",
+        orphans.len(),
+        total
+    );
+    for addr in orphans.iter() {
+        content.push_str(&format!("{}\n", addr));
+    }
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(content.as_bytes())
+}
+
+/// Prompt for a secret (e.g. a passphrase) without echoing it, optionally asking for it twice.
+fn prompt_secret(name: &str, confirm: bool) -> SecretString {
+    loop {
+        let secret = match rpassword::read_password_from_tty(Some(&format!("{}: ", name))) {
+            Ok(secret) => SecretString::from(secret),
+            Err(e) => {
+                debug!("{}", e);
+                process::exit(255);
+            }
+        };
+
+        if secret.is_empty() {
+            warn!("the provided {} is empty", name.to_lowercase());
+        }
+
+        if !confirm {
+            return secret;
+        }
+
+        match rpassword::read_password_from_tty(Some(&format!("Confirm {}: ", name.to_lowercase())))
+        {
+            Ok(confirmation) if SecretString::from(confirmation) == secret => return secret,
+            Ok(_) => {
+                eprintln!("{}s do not match, please try again.", name);
+                continue;
+            }
+            Err(e) => {
+                debug!("{}", e);
+                process::exit(255);
+            }
+        }
+    }
+}
+
+/// Query the bech32 HRP of the network the Chrysalis node is on.
+async fn query_bech32_hrp(node: &str) -> Result<String, iota_client::Error> {
+    iota_client::Client::builder()
+        .with_node(node)?
+        .with_node_sync_disabled()
+        .finish()
+        .await?
+        .get_bech32_hrp()
+        .await
 }
@@ -0,0 +1,111 @@
+use crate::addrs::{AddrInfo, Addrs};
+use crate::args::Args;
+use crate::seeds::Seeds;
+#[allow(deprecated)]
+use iota_legacy::crypto::hashes::ternary::kerl::Kerl;
+use iota_legacy::crypto::keys::ternary::seed::Seed;
+#[allow(deprecated)]
+use iota_legacy::crypto::keys::ternary::wots::sponge::WotsSpongePrivateKeyGeneratorBuilder;
+#[allow(deprecated)]
+use iota_legacy::crypto::keys::ternary::wots::WotsSecurityLevel;
+use iota_legacy::crypto::keys::ternary::PrivateKeyGenerator;
+use iota_legacy::crypto::signatures::ternary::PrivateKey;
+use iota_legacy::crypto::signatures::ternary::PublicKey;
+use iota_legacy::ternary::T3B1Buf;
+use log::*;
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// Derived addresses, keyed by the position of the seed, the address index and the security level.
+type AddressCache = HashMap<(usize, usize, u8), String>;
+
+/// Derive the address (without checksum) of [seed] at [index] with [security_level].
+#[allow(deprecated)]
+fn derive_address(seed: &str, index: usize, security_level: u8) -> String {
+    let seed_ternary: Seed = seed.parse().unwrap(); // we've validated it during parsing
+
+    let security_level_wot = match security_level {
+        1 => WotsSecurityLevel::Low,
+        2 => WotsSecurityLevel::Medium,
+        3 => WotsSecurityLevel::High,
+        _ => unreachable!(), // this should have been checked during CLI parsing
+    };
+
+    WotsSpongePrivateKeyGeneratorBuilder::<Kerl>::default()
+        .with_security_level(security_level_wot)
+        .build()
+        .unwrap()
+        .generate_from_seed(&seed_ternary, index)
+        .unwrap()
+        .generate_public_key()
+        .unwrap()
+        .as_trits()
+        .encode::<T3B1Buf>()
+        .iter_trytes()
+        .map(char::from)
+        .collect()
+}
+
+/// Assign every address to the seed it belongs to. Each seed derives its addresses only once per
+/// index listed, instead of once per address per seed.
+///
+/// Returns the addresses of each seed (in the same order as [seeds]), and the addresses that
+/// belong to no seed.
+pub fn assign(args: &Args, seeds: &Seeds, addrs: &Addrs) -> (Vec<Addrs>, Addrs) {
+    let indices: BTreeSet<usize> = addrs.iter().map(|addr| addr.idx).collect();
+    let keys: Vec<(usize, usize, u8)> = (0..seeds.len())
+        .flat_map(|i| {
+            indices
+                .iter()
+                .map(move |&idx| (i, idx, args.security_level))
+        })
+        .collect();
+
+    debug!(
+        "deriving {} addresses for {} seeds and {} indices",
+        keys.len(),
+        seeds.len(),
+        indices.len()
+    );
+
+    // The task to run below, regardless of parallelism
+    let derive = |&(i, idx, security_level): &(usize, usize, u8)| {
+        let address = derive_address(seeds[i].seed.expose(), idx, security_level);
+        ((i, idx, security_level), address)
+    };
+
+    let cache: AddressCache = if args.parallel_mode.is_parallel_search() {
+        keys.par_iter().map(derive).collect()
+    } else {
+        keys.iter().map(derive).collect()
+    };
+
+    let mut assigned: Vec<Vec<AddrInfo>> = vec![Vec::new(); seeds.len()];
+    let mut orphans = Vec::new();
+
+    for addr in addrs.iter() {
+        let mut matched = false;
+
+        for (i, seed) in seeds.iter().enumerate() {
+            if cache[&(i, addr.idx, args.security_level)] == addr.addr {
+                debug!(
+                    "seed {}: accept matching address {}",
+                    seed.name(args.unsafe_print_seeds),
+                    addr.addr
+                );
+                assigned[i].push(addr.clone());
+                matched = true;
+            }
+        }
+
+        if !matched {
+            warn!("address {} matches no seed", addr.addr);
+            orphans.push(addr.clone());
+        }
+    }
+
+    (
+        assigned.into_iter().map(Addrs::from_inner).collect(),
+        Addrs::from_inner(orphans),
+    )
+}
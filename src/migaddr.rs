@@ -0,0 +1,95 @@
+use iota_client::bee_message::address::{Address as ChrysalisAddress, Ed25519Address};
+use iota_legacy::client::migration;
+use iota_legacy::client::migration::encode_migration_address;
+use iota_legacy::ternary::{b1t6, T1B1Buf, T3B1Buf, TryteBuf};
+use iota_legacy::transaction::bundled::{Address, BundledTransactionField};
+
+/// Migration addresses always start with this prefix, followed by the b1t6-encoded Ed25519
+/// address with a 4-byte checksum, then padded with a single 9.
+const MIGRATION_ADDRESS_PREFIX: &str = "TRANSFER";
+
+/// Decode a legacy migration address (81 trytes, or 90 trytes with checksum) back into the
+/// Chrysalis Ed25519 address it encodes.
+pub fn decode(addr: &str) -> Result<Ed25519Address, &'static str> {
+    let addr = addr.trim();
+
+    let addr_81 = match addr.len() {
+        81 => addr,
+        90 => {
+            let addr_81 = &addr[..81];
+
+            if add_checksum(addr_81)? != addr {
+                return Err("the address checksum is invalid");
+            }
+
+            addr_81
+        }
+        _ => return Err("a legacy address must be 81 or 90 trytes long"),
+    };
+
+    if !addr_81.starts_with(MIGRATION_ADDRESS_PREFIX) || !addr_81.ends_with('9') {
+        return Err("not a migration address");
+    }
+
+    let payload = TryteBuf::try_from_str(&addr_81[MIGRATION_ADDRESS_PREFIX.len()..80])
+        .map_err(|_| "failed to parse the address into trytes")?
+        .as_trits()
+        .encode::<T1B1Buf>();
+    let bytes = b1t6::decode(&payload).map_err(|_| "failed to decode the address payload")?;
+
+    if bytes.len() != 36 {
+        return Err("the address payload has a wrong length");
+    }
+
+    let mut ed25519 = [0u8; 32];
+    ed25519.copy_from_slice(&bytes[..32]);
+    let ed25519 = Ed25519Address::new(ed25519);
+
+    // Encode it back to verify the embedded 4-byte checksum
+    if encode(ed25519)? != addr_81 {
+        return Err("the embedded Ed25519 address checksum is invalid");
+    }
+
+    Ok(ed25519)
+}
+
+/// Encode a Chrysalis Ed25519 address into its 81-tryte legacy migration address.
+pub fn encode(ed25519: Ed25519Address) -> Result<String, &'static str> {
+    let addr = encode_migration_address(ed25519).map_err(|_| "failed to encode the address")?;
+
+    Ok(addr
+        .to_inner()
+        .encode::<T3B1Buf>()
+        .iter_trytes()
+        .map(char::from)
+        .collect())
+}
+
+/// Append the 9-tryte checksum to an 81-tryte legacy address.
+pub fn add_checksum(addr: &str) -> Result<String, &'static str> {
+    let addr = Address::try_from_inner(
+        TryteBuf::try_from_str(addr)
+            .map_err(|_| "failed to parse the address into trytes")?
+            .as_trits()
+            .encode(),
+    )
+    .map_err(|_| "not a valid legacy address")?;
+
+    migration::add_tryte_checksum(addr).map_err(|_| "failed to compute the address checksum")
+}
+
+/// Parse a Chrysalis bech32 address of any HRP, returning the HRP and the Ed25519 address.
+pub fn parse_bech32(addr: &str) -> Result<(String, Ed25519Address), &'static str> {
+    let addr = addr.trim();
+
+    // The data part never contains a 1, so the last one is the separator
+    let hrp = match addr.rfind('1') {
+        Some(pos) if pos > 0 => addr[..pos].to_owned(),
+        _ => return Err("not a bech32 address"),
+    };
+
+    match ChrysalisAddress::try_from_bech32(addr) {
+        Ok(ChrysalisAddress::Ed25519(ed25519)) => Ok((hrp, ed25519)),
+        Err(_) => Err("not a valid bech32 address"),
+    }
+}
@@ -0,0 +1,202 @@
+use crate::args::Args;
+use crate::throttle::Throttle;
+use iota_legacy::client::Client as LegacyClient;
+use iota_legacy::crypto::hashes::ternary::Hash;
+use iota_legacy::ternary::Btrit;
+use iota_legacy::transaction::bundled::BundledTransactionField;
+use log::*;
+use std::fmt;
+
+const LEGACY_MAINNET_NODE_URL: &str = "https://nodes.iota.org";
+const CHRYSALIS_MAINNET_NODE_URL: &str = "https://chrysalis-nodes.iota.org";
+const LEGACY_TESTNET_NODE_URL: &str = "https://nodes-legacy.iotatestmigration6.net";
+const CHRYSALIS_TESTNET_NODE_URL: &str = "https://api.lb-0.h.migration6.iotatestmigration6.net";
+const PERMANODE_URL: &str = "https://chronicle.iota.org/api";
+
+/// The coordinator issuing the milestones of the legacy mainnet.
+const MAINNET_COORDINATOR_ADDRESS: &str =
+    "UDYXTZBE9GZGPM9SSQV9LTZNDLJIZMPUVVXYXFYVBLIEUHLSEWFTKZZLXYRHHWVQV9MNNX9KZC9D9UZWZ";
+
+/// The legacy network to migrate from, which decides the parameters of sending transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Custom,
+}
+
+impl Network {
+    /// The default legacy node, if the network has one.
+    pub fn legacy_node(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some(LEGACY_MAINNET_NODE_URL),
+            Self::Testnet => Some(LEGACY_TESTNET_NODE_URL),
+            Self::Custom => None,
+        }
+    }
+
+    /// The default Chrysalis node, if the network has one.
+    pub fn chrysalis_node(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some(CHRYSALIS_MAINNET_NODE_URL),
+            Self::Testnet => Some(CHRYSALIS_TESTNET_NODE_URL),
+            Self::Custom => None,
+        }
+    }
+
+    /// The default Permanode, if the network has one.
+    pub fn permanode(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some(PERMANODE_URL),
+            Self::Testnet => Some(PERMANODE_URL),
+            Self::Custom => None,
+        }
+    }
+
+    /// The bech32 HRP of the Chrysalis network that funds are migrated to, if it's known.
+    pub fn hrp(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some("iota"),
+            Self::Testnet => Some("atoi"),
+            Self::Custom => None,
+        }
+    }
+
+    /// The minimum weight magnitude the network requires, if it's known.
+    pub fn minimum_weight_magnitude(&self) -> Option<u8> {
+        match self {
+            Self::Mainnet => Some(14),
+            Self::Testnet => Some(14),
+            Self::Custom => None,
+        }
+    }
+
+    /// The depth of tip selection that nodes of the network accept, if it's known.
+    pub fn depth(&self) -> Option<u8> {
+        match self {
+            Self::Mainnet => Some(2),
+            Self::Testnet => Some(2),
+            Self::Custom => None,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Testnet => write!(f, "testnet"),
+            Self::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+/// How many trits at the end of [hash] are 0, i.e. the highest MWM it satisfies.
+fn trailing_zeros(hash: &Hash) -> usize {
+    hash.to_inner()
+        .iter()
+        .rev()
+        .take_while(|trit| *trit == Btrit::Zero)
+        .count()
+}
+
+/// How many times tips are selected to estimate the MWM of the network. Each tip has at least as
+/// many trailing 0s as the network requires, and one more with a chance of 1/3, so the fewest
+/// trailing 0s of 8 tips overestimates the MWM with a chance of only 1/3^8.
+const TIP_SAMPLES: usize = 4;
+
+/// Check the legacy node and the MWM and depth to send transactions with before sending anything.
+/// It's an error if the legacy node is on the mainnet while another network is selected or the
+/// other way round, as the mainnet confirmation is only asked for the selected network. Mismatches
+/// of the MWM and depth are only warned about, as the MWM of the network is only estimated from
+/// its tips, and the node may be wrong as well.
+pub async fn check(args: &Args, client: &LegacyClient, throttle: &Throttle) -> Result<(), ()> {
+    let info = throttle
+        .run("query the legacy node", || client.get_node_info())
+        .await
+        .map_err(|e| error!("failed to query the network of the legacy node: {}", e))?;
+
+    // The coordinator address may come with a checksum
+    let on_mainnet = info
+        .coordinator_address
+        .starts_with(MAINNET_COORDINATOR_ADDRESS);
+    if on_mainnet != (args.network == Network::Mainnet) {
+        error!(
+            "the legacy node {} is {}on the mainnet (coordinator {}), but the network to migrate \
+            on is {}",
+            args.legacy_node,
+            if on_mainnet { "" } else { "not " },
+            info.coordinator_address,
+            args.network
+        );
+        return Err(());
+    }
+
+    debug!(
+        "the legacy node is on the {} network (coordinator {})",
+        args.network, info.coordinator_address
+    );
+
+    match args.network.minimum_weight_magnitude() {
+        Some(profile_mwm) if args.minimum_weight_magnitude != profile_mwm => warn!(
+            "the minimum weight magnitude {} differs from {} of the {} network",
+            args.minimum_weight_magnitude, profile_mwm, args.network
+        ),
+        _ => (),
+    }
+
+    let mut tips_mwm = usize::MAX;
+    for _ in 0..TIP_SAMPLES {
+        // The node refuses to select tips if the depth is out of its range
+        let tips = throttle
+            .run("select tips", || {
+                client
+                    .get_transactions_to_approve()
+                    .depth(args.depth)
+                    .send()
+            })
+            .await;
+
+        match tips {
+            Ok(tips) => {
+                tips_mwm = tips_mwm
+                    .min(trailing_zeros(&tips.trunk_transaction))
+                    .min(trailing_zeros(&tips.branch_transaction));
+            }
+            Err(e) => {
+                warn!(
+                    "the legacy node cannot select tips with depth {}: {}",
+                    args.depth, e
+                );
+                return Ok(());
+            }
+        }
+    }
+
+    // Every tip has passed the proof of work of the network, so the network can't require more
+    // than what the tips have, and almost certainly requires exactly that
+    let mwm = args.minimum_weight_magnitude as usize;
+    if mwm > tips_mwm {
+        warn!(
+            "the minimum weight magnitude {} is higher than {} of the tips on the legacy node, \
+            which costs more proof of work than needed",
+            mwm, tips_mwm
+        );
+    } else if mwm < tips_mwm {
+        warn!(
+            "the minimum weight magnitude {} is lower than {} of the tips on the legacy node, \
+            which is probably what the network requires (estimated from {} tips); the node may \
+            reject the bundles",
+            mwm,
+            tips_mwm,
+            TIP_SAMPLES * 2
+        );
+    } else {
+        debug!(
+            "the legacy node accepts depth {}, and its tips have the minimum weight magnitude {}",
+            args.depth, mwm
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,543 @@
+use crate::throttle::Throttle;
+use iota_legacy::client::Client as LegacyClient;
+use iota_legacy::crypto::hashes::ternary::curl_p::CurlP81;
+use iota_legacy::crypto::hashes::ternary::{Hash, Sponge};
+use iota_legacy::ternary::{Btrit, T1B1Buf, TritBuf};
+use iota_legacy::transaction::bundled::{BundledTransaction, BundledTransactionField};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Transaction layout, in trits
+const TRANSACTION_LENGTH: usize = 8019;
+const TRUNK_OFFSET: usize = 7290;
+const BRANCH_OFFSET: usize = 7533;
+const ATTACHMENT_TIMESTAMP_OFFSET: usize = 7857;
+const ATTACHMENT_TIMESTAMP_LOWER_BOUND_OFFSET: usize = 7884;
+const ATTACHMENT_TIMESTAMP_UPPER_BOUND_OFFSET: usize = 7911;
+const TIMESTAMP_LENGTH: usize = 27;
+
+/// The largest number that fits into a 27-trit timestamp, i.e. (3^27 - 1) / 2.
+const MAX_TIMESTAMP: i64 = 3_812_798_742_493;
+
+// Curl-P-81
+const HASH_LENGTH: usize = 243;
+const STATE_LENGTH: usize = 3 * HASH_LENGTH;
+const NUM_ROUNDS: usize = 81;
+
+/// Where the nonce starts in the last 243-trit chunk of a transaction.
+const NONCE_OFFSET: usize = 162;
+
+/// The first nonce trits tell the 64 lanes of a search apart, the next ones tell the threads
+/// apart, and the rest are incremented by each thread.
+const LANE_TRITS: usize = 4;
+const THREAD_TRITS: usize = 5;
+const INCREMENT_OFFSET: usize = NONCE_OFFSET + LANE_TRITS + THREAD_TRITS;
+
+/// How many threads can search at once, i.e. how many different values the thread trits have.
+pub const MAX_THREADS: usize = 243;
+
+/// A Curl-P-81 state of 64 hashes computed at once. Each trit is stored in two bit vectors, one bit
+/// per hash: -1 as (1, 0), 0 as (1, 1), and +1 as (0, 1).
+#[derive(Clone)]
+struct State {
+    lo: [u64; STATE_LENGTH],
+    hi: [u64; STATE_LENGTH],
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            lo: [!0; STATE_LENGTH],
+            hi: [!0; STATE_LENGTH],
+        }
+    }
+
+    /// Set the trit at [index] to [trit] in every lane.
+    fn set(&mut self, index: usize, trit: i8) {
+        let (lo, hi) = match trit {
+            -1 => (!0, 0),
+            1 => (0, !0),
+            _ => (!0, !0),
+        };
+
+        self.lo[index] = lo;
+        self.hi[index] = hi;
+    }
+
+    /// The trit at [index] in [lane].
+    fn get(&self, index: usize, lane: u32) -> i8 {
+        match ((self.lo[index] >> lane) & 1, (self.hi[index] >> lane) & 1) {
+            (1, 0) => -1,
+            (0, 1) => 1,
+            _ => 0,
+        }
+    }
+
+    fn absorb(&mut self, chunk: &[i8]) {
+        for (i, &trit) in chunk.iter().enumerate() {
+            self.set(i, trit);
+        }
+
+        self.transform();
+    }
+
+    fn transform(&mut self) {
+        let mut scratch = self.clone();
+
+        for _ in 0..NUM_ROUNDS {
+            std::mem::swap(self, &mut scratch);
+
+            let mut index = 0;
+            for i in 0..STATE_LENGTH {
+                let (x_lo, x_hi) = (scratch.lo[index], scratch.hi[index]);
+                index = if index < 365 {
+                    index + 364
+                } else {
+                    index - 365
+                };
+                let (y_lo, y_hi) = (scratch.lo[index], scratch.hi[index]);
+
+                let d = x_hi ^ y_lo;
+                self.lo[i] = !(d & x_lo);
+                self.hi[i] = d | (x_lo ^ y_hi);
+            }
+        }
+    }
+
+    /// Increment the trits in [from..to], which are the same in every lane, as a number. Returns
+    /// false if it overflows.
+    fn increment(&mut self, from: usize, to: usize) -> bool {
+        for i in from..to {
+            match (self.lo[i], self.hi[i]) {
+                (_, 0) => {
+                    // -1 -> 0
+                    self.hi[i] = !0;
+                    return true;
+                }
+                (0, _) => {
+                    // 1 -> -1, carry
+                    self.lo[i] = !0;
+                    self.hi[i] = 0;
+                }
+                _ => {
+                    // 0 -> 1
+                    self.lo[i] = 0;
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Encode [value] into balanced trits, least significant first.
+fn write_number(trits: &mut [i8], mut value: i64) {
+    for trit in trits.iter_mut() {
+        let remainder = value.rem_euclid(3);
+        value = value.div_euclid(3);
+
+        *trit = if remainder == 2 {
+            value += 1;
+            -1
+        } else {
+            remainder as i8
+        };
+    }
+}
+
+fn trit_buf(trits: &[i8]) -> TritBuf<T1B1Buf> {
+    trits
+        .iter()
+        .map(|&trit| Btrit::try_from(trit).unwrap())
+        .collect()
+}
+
+fn transaction_trits(transaction: &BundledTransaction) -> Vec<i8> {
+    let mut trits = TritBuf::<T1B1Buf>::zeros(TRANSACTION_LENGTH);
+    transaction.as_trits_allocated(&mut trits);
+    trits.iter().map(i8::from).collect()
+}
+
+/// The Curl-P-81 hash of [transaction], computed by the library instead of [State] so that the
+/// search is checked against an independent implementation.
+fn hash(transaction: &[i8]) -> Vec<i8> {
+    CurlP81::new()
+        .digest(&trit_buf(transaction))
+        .unwrap()
+        .iter()
+        .map(i8::from)
+        .collect()
+}
+
+/// The nonce trits telling [thread] apart from the other threads, for [thread] < [MAX_THREADS].
+fn thread_trits(thread: usize) -> [i8; THREAD_TRITS] {
+    // Balanced trits range from -(MAX_THREADS - 1) / 2 to (MAX_THREADS - 1) / 2
+    let mut trits = [0i8; THREAD_TRITS];
+    write_number(&mut trits, thread as i64 - (MAX_THREADS as i64 - 1) / 2);
+    trits
+}
+
+/// Search the nonce of [transaction] in one thread, with the nonce trits of [thread] fixed. Gives
+/// up when [found] is set by another thread.
+fn search_in_thread(
+    mut state: State,
+    min_weight_magnitude: usize,
+    thread: usize,
+    found: &AtomicBool,
+) -> Option<Vec<i8>> {
+    for (i, &trit) in thread_trits(thread).iter().enumerate() {
+        state.set(NONCE_OFFSET + LANE_TRITS + i, trit);
+    }
+
+    while !found.load(Ordering::Relaxed) {
+        let mut hashed = state.clone();
+        hashed.transform();
+
+        // A trit is 0 where both of its bits are the same
+        let mut lanes = !0u64;
+        for i in HASH_LENGTH - min_weight_magnitude..HASH_LENGTH {
+            lanes &= !(hashed.lo[i] ^ hashed.hi[i]);
+        }
+
+        if lanes != 0 {
+            found.store(true, Ordering::Relaxed);
+
+            let lane = lanes.trailing_zeros();
+            return Some(
+                (NONCE_OFFSET..HASH_LENGTH)
+                    .map(|i| state.get(i, lane))
+                    .collect(),
+            );
+        }
+
+        if !state.increment(INCREMENT_OFFSET, HASH_LENGTH) {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Find a nonce for [transaction] so that its hash ends with [min_weight_magnitude] 0s, using up
+/// to [threads] threads (at most [MAX_THREADS]), and write it into the transaction.
+fn search(
+    transaction: &mut [i8],
+    min_weight_magnitude: usize,
+    threads: usize,
+) -> Result<(), &'static str> {
+    let last_chunk = TRANSACTION_LENGTH - HASH_LENGTH;
+
+    // Everything but the last chunk is the same in every try
+    let mut state = State::new();
+    for chunk in transaction[..last_chunk].chunks(HASH_LENGTH) {
+        state.absorb(chunk);
+    }
+    for (i, &trit) in transaction[last_chunk..].iter().enumerate() {
+        state.set(i, trit);
+    }
+
+    // Give each of the 64 lanes a different nonce
+    for i in 0..LANE_TRITS {
+        let (mut lo, mut hi) = (0u64, 0u64);
+
+        for lane in 0..64 {
+            let mut lane_trits = [0i8; LANE_TRITS];
+            write_number(&mut lane_trits, lane as i64);
+
+            match lane_trits[i] {
+                -1 => lo |= 1 << lane,
+                1 => hi |= 1 << lane,
+                _ => {
+                    lo |= 1 << lane;
+                    hi |= 1 << lane;
+                }
+            }
+        }
+
+        state.lo[NONCE_OFFSET + i] = lo;
+        state.hi[NONCE_OFFSET + i] = hi;
+    }
+
+    let found = AtomicBool::new(false);
+    let nonce = std::thread::scope(|scope| {
+        let searches: Vec<_> = (0..threads.clamp(1, MAX_THREADS))
+            .map(|thread| {
+                let (state, found) = (state.clone(), &found);
+                scope.spawn(move || search_in_thread(state, min_weight_magnitude, thread, found))
+            })
+            .collect();
+
+        searches
+            .into_iter()
+            .filter_map(|search| search.join().unwrap())
+            .next()
+    });
+
+    // The nonce space is far too large to be exhausted in practice
+    let nonce = nonce.ok_or("no nonce is found")?;
+    transaction[last_chunk + NONCE_OFFSET..].copy_from_slice(&nonce);
+
+    Ok(())
+}
+
+/// Attach the transactions of [bundle] (in the order of their indices) on top of [trunk] and
+/// [branch], doing the proof of work locally with up to [threads] threads per transaction.
+fn attach_locally(
+    bundle: &[BundledTransaction],
+    trunk: &[i8],
+    branch: &[i8],
+    min_weight_magnitude: u8,
+    threads: usize,
+) -> Result<Vec<BundledTransaction>, &'static str> {
+    let mut transactions: Vec<Vec<i8>> = bundle.iter().map(transaction_trits).collect();
+
+    // The last transaction is attached to the tips, then each one is attached to the next one
+    let mut next_hash: Option<Vec<i8>> = None;
+    for transaction in transactions.iter_mut().rev() {
+        let (trunk, branch) = match next_hash {
+            Some(ref next_hash) => (&next_hash[..], trunk),
+            None => (trunk, branch),
+        };
+        transaction[TRUNK_OFFSET..TRUNK_OFFSET + HASH_LENGTH].copy_from_slice(trunk);
+        transaction[BRANCH_OFFSET..BRANCH_OFFSET + HASH_LENGTH].copy_from_slice(branch);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let timestamps = [
+            (ATTACHMENT_TIMESTAMP_OFFSET, now),
+            (ATTACHMENT_TIMESTAMP_LOWER_BOUND_OFFSET, 0),
+            (ATTACHMENT_TIMESTAMP_UPPER_BOUND_OFFSET, MAX_TIMESTAMP),
+        ];
+        for (offset, value) in timestamps {
+            write_number(&mut transaction[offset..offset + TIMESTAMP_LENGTH], value);
+        }
+
+        search(transaction, min_weight_magnitude as usize, threads)?;
+        next_hash = Some(hash(transaction));
+    }
+
+    transactions
+        .iter()
+        .map(|trits| {
+            BundledTransaction::from_trits(&trit_buf(trits))
+                .map_err(|_| "invalid transaction after the proof of work")
+        })
+        .collect()
+}
+
+/// The trits of a transaction hash, e.g. a tip, as taken by [attach_locally].
+fn hash_trits(hash: &Hash) -> Vec<i8> {
+    hash.to_inner().iter().map(i8::from).collect()
+}
+
+/// Check that [attached] is [bundle] (in the order of their indices) attached on top of [trunk]
+/// and [branch]: every transaction approves the next one and [trunk], except for the last one,
+/// which approves [trunk] and [branch], and all of them belong to the bundle and have their proof
+/// of work done.
+pub fn check_attached(
+    bundle: &[BundledTransaction],
+    attached: &[BundledTransaction],
+    trunk: &Hash,
+    branch: &Hash,
+    min_weight_magnitude: u8,
+) -> Result<(), &'static str> {
+    if attached.len() != bundle.len() {
+        return Err("the number of transactions differs from the bundle");
+    }
+
+    let hashes: Vec<Vec<i8>> = attached
+        .iter()
+        .map(|transaction| hash(&transaction_trits(transaction)))
+        .collect();
+    let (trunk, branch) = (hash_trits(trunk), hash_trits(branch));
+
+    for (i, transaction) in attached.iter().enumerate() {
+        if transaction.bundle() != bundle[i].bundle() {
+            return Err("a transaction doesn't belong to the bundle");
+        }
+
+        let min_weight_magnitude = min_weight_magnitude as usize;
+        if hashes[i][HASH_LENGTH - min_weight_magnitude..]
+            .iter()
+            .any(|&trit| trit != 0)
+        {
+            return Err("the proof of work of a transaction isn't done");
+        }
+
+        let (expected_trunk, expected_branch) = match hashes.get(i + 1) {
+            Some(next_hash) => (next_hash, &trunk),
+            None => (&trunk, &branch),
+        };
+        if hash_trits(transaction.trunk()) != *expected_trunk
+            || hash_trits(transaction.branch()) != *expected_branch
+        {
+            return Err("the transactions aren't chained in the order of their indices");
+        }
+    }
+
+    Ok(())
+}
+
+/// Where the proof of work of transactions is done.
+#[derive(Clone)]
+pub enum PowProvider {
+    /// Locally, with up to this many threads per transaction.
+    Local(usize),
+    /// By the attachToTangle API of a node, which can be the legacy node itself, or a dedicated
+    /// proof of work service.
+    Remote(LegacyClient),
+}
+
+impl PowProvider {
+    /// Attach the transactions of [bundle] on top of [trunk] and [branch].
+    pub async fn attach(
+        &self,
+        throttle: &Throttle,
+        bundle: &[BundledTransaction],
+        trunk: &Hash,
+        branch: &Hash,
+        min_weight_magnitude: u8,
+    ) -> Result<Vec<BundledTransaction>, String> {
+        match self {
+            Self::Local(threads) => {
+                let (trunk, branch, threads) = (hash_trits(trunk), hash_trits(branch), *threads);
+
+                // The proof of work would block the asynchronous runtime otherwise
+                let bundle = bundle.to_vec();
+                tokio::task::spawn_blocking(move || {
+                    attach_locally(&bundle, &trunk, &branch, min_weight_magnitude, threads)
+                })
+                .await
+                .unwrap()
+                .map_err(|e| e.to_owned())
+            }
+            Self::Remote(client) => {
+                // attachToTangle takes the transactions from the last one to the first one, and
+                // attaches each of them on top of the one taken before it
+                let trytes: Vec<_> = bundle.iter().rev().cloned().collect();
+                let response = throttle
+                    .run("do the proof of work remotely", || {
+                        client
+                            .attach_to_tangle()
+                            .trunk_transaction(trunk)
+                            .branch_transaction(branch)
+                            .min_weight_magnitude(min_weight_magnitude)
+                            .trytes(&trytes)
+                            .send()
+                    })
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                let mut attached = response.trytes;
+                attached.sort_by_key(|transaction| *transaction.index().to_inner());
+
+                Ok(attached)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trits that look random enough, but are the same in every run.
+    fn pseudo_random_trits(len: usize, mut state: u64) -> Vec<i8> {
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                ((state >> 33) % 3) as i8 - 1
+            })
+            .collect()
+    }
+
+    fn zero_bundle(len: usize) -> Vec<BundledTransaction> {
+        (0..len)
+            .map(|_| {
+                BundledTransaction::from_trits(&TritBuf::<T1B1Buf>::zeros(TRANSACTION_LENGTH))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bitsliced_hash_matches_curl_p81() {
+        let transaction = pseudo_random_trits(TRANSACTION_LENGTH, 1);
+
+        let mut state = State::new();
+        for chunk in transaction.chunks(HASH_LENGTH) {
+            state.absorb(chunk);
+        }
+
+        for lane in [0, 31, 63] {
+            let bitsliced: Vec<i8> = (0..HASH_LENGTH).map(|i| state.get(i, lane)).collect();
+            assert_eq!(bitsliced, hash(&transaction));
+        }
+    }
+
+    #[test]
+    fn thread_trits_are_distinct() {
+        let mut all: Vec<_> = (0..MAX_THREADS).map(thread_trits).collect();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), MAX_THREADS);
+    }
+
+    #[test]
+    fn search_meets_min_weight_magnitude() {
+        let mut transaction = pseudo_random_trits(TRANSACTION_LENGTH, 2);
+        search(&mut transaction, 9, 4).unwrap();
+
+        let hash = hash(&transaction);
+        assert!(hash[HASH_LENGTH - 9..].iter().all(|&trit| trit == 0));
+    }
+
+    #[test]
+    fn attach_locally_chains_transactions() {
+        let (trunk, branch) = (
+            pseudo_random_trits(HASH_LENGTH, 3),
+            pseudo_random_trits(HASH_LENGTH, 4),
+        );
+        let bundle = zero_bundle(3);
+        let attached = attach_locally(&bundle, &trunk, &branch, 5, 2).unwrap();
+        let hashes: Vec<_> = attached
+            .iter()
+            .map(|tx| hash(&transaction_trits(tx)))
+            .collect();
+
+        for (i, transaction) in attached.iter().enumerate() {
+            assert!(hashes[i][HASH_LENGTH - 5..].iter().all(|&trit| trit == 0));
+
+            let (expected_trunk, expected_branch) = match hashes.get(i + 1) {
+                Some(next_hash) => (next_hash, &trunk),
+                None => (&trunk, &branch),
+            };
+            assert_eq!(&hash_trits(transaction.trunk()), expected_trunk);
+            assert_eq!(&hash_trits(transaction.branch()), expected_branch);
+        }
+    }
+
+    #[test]
+    fn check_attached_accepts_only_chained_bundles() {
+        let (trunk, branch) = (
+            Hash::try_from_inner(trit_buf(&pseudo_random_trits(HASH_LENGTH, 5))).unwrap(),
+            Hash::try_from_inner(trit_buf(&pseudo_random_trits(HASH_LENGTH, 6))).unwrap(),
+        );
+        let bundle = zero_bundle(2);
+        let attached =
+            attach_locally(&bundle, &hash_trits(&trunk), &hash_trits(&branch), 5, 2).unwrap();
+
+        assert!(check_attached(&bundle, &attached, &trunk, &branch, 5).is_ok());
+
+        let reversed: Vec<_> = attached.iter().rev().cloned().collect();
+        assert!(check_attached(&bundle, &reversed, &trunk, &branch, 5).is_err());
+        assert!(check_attached(&bundle, &attached, &branch, &trunk, 5).is_err());
+        assert!(check_attached(&bundle, &attached[..1], &trunk, &branch, 5).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use log::*;
+#[cfg(unix)]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(unix)]
+use std::sync::Mutex;
+use zeroize::Zeroize;
+
+/// How many live secrets are on each locked page, by page address. Locks don't nest, so a page is
+/// only unlocked when the last secret on it is dropped.
+#[cfg(unix)]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// The addresses of the pages that [len] bytes at [ptr] span.
+#[cfg(unix)]
+fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let first = ptr as usize / page_size;
+    let last = (ptr as usize + len - 1) / page_size;
+
+    (first..=last).map(move |page| page * page_size)
+}
+
+/// Try to keep [len] bytes at [ptr] out of swap. This is best effort: it may fail for example when
+/// RLIMIT_MEMLOCK is too low, in which case the memory is only zeroized on drop.
+fn lock(ptr: *const u8, len: usize) {
+    #[cfg(unix)]
+    if len > 0 {
+        let mut locked_pages = LOCKED_PAGES.lock().unwrap();
+
+        for page in pages(ptr, len) {
+            let count = locked_pages.entry(page).or_insert(0);
+            *count += 1;
+
+            if *count == 1 && unsafe { libc::mlock(page as *const libc::c_void, 1) } != 0 {
+                debug!(
+                    "failed to lock secret memory: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (ptr, len);
+}
+
+fn unlock(ptr: *const u8, len: usize) {
+    #[cfg(unix)]
+    if len > 0 {
+        let mut locked_pages = LOCKED_PAGES.lock().unwrap();
+
+        for page in pages(ptr, len) {
+            if let Some(count) = locked_pages.get_mut(&page) {
+                *count -= 1;
+
+                if *count == 0 {
+                    locked_pages.remove(&page);
+                    unsafe {
+                        libc::munlock(page as *const libc::c_void, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (ptr, len);
+}
+
+/// A string of secret material, e.g. a seed or a passphrase. It is zeroized on drop, never shown
+/// by [Debug], and kept out of swap where the platform allows.
+///
+/// The buffer is allocated once and never grows, so that the locked memory stays where it is.
+pub struct SecretString {
+    inner: String,
+}
+
+impl SecretString {
+    pub fn new(s: &str) -> Self {
+        let mut inner = String::with_capacity(s.len());
+        lock(inner.as_ptr(), inner.capacity());
+        inner.push_str(s);
+
+        Self { inner }
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.inner
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    /// Take over [s], zeroizing the original buffer.
+    fn from(mut s: String) -> Self {
+        let secret = Self::new(&s);
+        s.zeroize();
+
+        secret
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(&self.inner)
+    }
+}
+
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for SecretString {}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(***)")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.inner.as_ptr(), self.inner.capacity());
+
+        self.inner.zeroize();
+        unlock(ptr, capacity);
+    }
+}
+
+/// Fixed-size secret bytes, e.g. a BIP39 seed, with the same guarantees as [SecretString].
+pub struct SecretBytes<const N: usize> {
+    inner: Box<[u8; N]>,
+}
+
+impl<const N: usize> SecretBytes<N> {
+    pub fn zeroed() -> Self {
+        let inner = Box::new([0u8; N]);
+        lock(inner.as_ptr(), N);
+
+        Self { inner }
+    }
+
+    pub fn expose(&self) -> &[u8; N] {
+        &self.inner
+    }
+
+    pub fn expose_mut(&mut self) -> &mut [u8; N] {
+        &mut self.inner
+    }
+}
+
+impl<const N: usize> fmt::Debug for SecretBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBytes(***)")
+    }
+}
+
+impl<const N: usize> Drop for SecretBytes<N> {
+    fn drop(&mut self) {
+        self.inner.zeroize();
+        unlock(self.inner.as_ptr(), N);
+    }
+}
@@ -1,15 +1,134 @@
+use crate::secret::SecretString;
+#[allow(deprecated)]
+use iota_legacy::crypto::hashes::ternary::kerl::Kerl;
+use iota_legacy::crypto::hashes::ternary::Sponge;
 use iota_legacy::ternary::tryte::TryteBuf;
-use log::info;
+use iota_legacy::ternary::{T1B1Buf, T3B1Buf};
+use log::warn;
 use std::ops::Deref;
 use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// The length of a legacy seed in trytes.
+const SEED_LENGTH: usize = 81;
+
+#[derive(Debug, Clone)]
+pub struct SeedInfo {
+    pub seed: SecretString,
+    pub label: Option<String>,
+    /// Whether the seed was shorter than 81 trytes and has been padded with 9s.
+    pub padded: bool,
+}
+
+impl SeedInfo {
+    /// The 3-tryte checksum of the seed, as shown by Trinity.
+    pub fn checksum(&self) -> String {
+        self.hash()[78..].to_owned()
+    }
+
+    /// A 9-tryte fingerprint of the seed, which ends with its checksum. It identifies the seed in
+    /// logs and reports without revealing it; the checksum alone collides too easily.
+    pub fn fingerprint(&self) -> String {
+        self.hash()[72..].to_owned()
+    }
+
+    /// How the seed is shown to the user: the fingerprint followed by the label if any, or the
+    /// full seed if [reveal] is set.
+    pub fn name(&self, reveal: bool) -> String {
+        let id = if reveal {
+            self.seed.expose().to_owned()
+        } else {
+            self.fingerprint()
+        };
+
+        match self.label {
+            Some(ref label) => format!("{} ({})", id, label),
+            None => id,
+        }
+    }
+
+    /// The Kerl hash of the seed in trytes.
+    #[allow(deprecated)]
+    fn hash(&self) -> String {
+        let trits = TryteBuf::try_from_str(self.seed.expose())
+            .unwrap() // we've validated and padded it during parsing
+            .as_trits()
+            .encode::<T1B1Buf>();
+        Kerl::default()
+            .digest(&trits)
+            .unwrap()
+            .encode::<T3B1Buf>()
+            .iter_trytes()
+            .map(char::from)
+            .collect()
+    }
+}
+
+impl FromStr for SeedInfo {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.is_empty() {
+            return Err("the seed is empty");
+        }
+
+        if s.len() > SEED_LENGTH {
+            return Err("the seed is longer than 81 trytes");
+        }
+
+        if s.chars().any(|c| c.is_ascii_lowercase()) {
+            return Err("the seed contains lowercase letters, which are not trytes");
+        }
+
+        if TryteBuf::try_from_str(s).is_err() {
+            return Err("failed to parse the seed into trytes");
+        }
+
+        // Short seeds are padded with 9s to 81 trytes, the same as Trinity does, so that the same
+        // addresses are derived from them. It's up to the caller to warn about it, where it knows
+        // where the seed comes from
+        let mut padded = Zeroizing::new(String::with_capacity(SEED_LENGTH));
+        padded.push_str(s);
+        while padded.len() < SEED_LENGTH {
+            padded.push('9');
+        }
+
+        Ok(Self {
+            seed: SecretString::new(&padded),
+            label: None,
+            padded: s.len() < SEED_LENGTH,
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct Seeds {
-    inner: Vec<String>,
+    inner: Vec<SeedInfo>,
+}
+
+impl Seeds {
+    /// Collect seeds, dropping any duplicate (keeping the first one), so that the same addresses
+    /// are never migrated twice.
+    pub fn from_inner(seeds: Vec<SeedInfo>) -> Self {
+        let mut inner: Vec<SeedInfo> = Vec::with_capacity(seeds.len());
+
+        for seed in seeds {
+            if inner.iter().any(|s| s.seed == seed.seed) {
+                warn!("dropping duplicate seed {}", seed.name(false));
+                continue;
+            }
+
+            inner.push(seed);
+        }
+
+        Self { inner }
+    }
 }
 
 impl Deref for Seeds {
-    type Target = Vec<String>;
+    type Target = Vec<SeedInfo>;
 
     fn deref(&self) -> &Self::Target {
         &self.inner
@@ -23,22 +142,81 @@ impl FromStr for Seeds {
         let mut inner = Vec::new();
 
         for (i, l) in s.lines().enumerate() {
-            // Ignore empty lines
-            if l.is_empty() {
+            // Ignore empty lines and comments
+            if l.trim().is_empty() || l.trim_start().starts_with('#') {
                 continue;
             }
 
-            let seed_tryte = TryteBuf::try_from_str(l);
+            // A seed can be followed by a label, separated by whitespaces
+            let l = l.trim();
+            let (seed, label) = match l.split_once(char::is_whitespace) {
+                Some((seed, label)) => (seed, Some(label.trim().to_owned())),
+                None => (l, None),
+            };
 
-            if let Err(err) = seed_tryte {
-                // Silently ignore any unrecognized line
-                info!("silently ignoring line {}: {}", i + 1, err);
-                continue;
-            }
+            match seed.parse::<SeedInfo>() {
+                Ok(mut info) => {
+                    if info.padded {
+                        warn!(
+                            "line {}: seed {} is shorter than 81 trytes, padded with 9s",
+                            i + 1,
+                            info.fingerprint()
+                        );
+                    }
 
-            inner.push(seed_tryte.unwrap().to_string());
+                    info.label = label;
+                    inner.push(info);
+                }
+                Err(err) => {
+                    warn!("ignoring line {}: {}", i + 1, err);
+                    continue;
+                }
+            };
         }
 
-        Ok(Self { inner })
+        Ok(Self::from_inner(inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str =
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9";
+
+    #[test]
+    fn short_seeds_are_padded() {
+        let info = "ABC".parse::<SeedInfo>().unwrap();
+        assert_eq!(info.seed.expose(), "ABC".to_owned() + &"9".repeat(78));
+        assert!(info.padded);
+
+        let info = SEED.parse::<SeedInfo>().unwrap();
+        assert_eq!(info.seed.expose(), SEED);
+        assert!(!info.padded);
+    }
+
+    #[test]
+    fn invalid_seeds_are_rejected() {
+        assert!("".parse::<SeedInfo>().is_err());
+        assert!("  ".parse::<SeedInfo>().is_err());
+        assert!(SEED.to_lowercase().parse::<SeedInfo>().is_err());
+        assert!((SEED.to_owned() + "A").parse::<SeedInfo>().is_err());
+        assert!("ABC1".parse::<SeedInfo>().is_err());
+    }
+
+    #[test]
+    fn seeds_file() {
+        let file = format!(
+            "# comment\n\n  {} savings account \n  # indented comment\nABC\n{}\nabc\nAB-C\n",
+            SEED, SEED
+        );
+        let seeds = file.parse::<Seeds>().unwrap();
+
+        assert_eq!(seeds.len(), 2);
+        assert_eq!(seeds[0].seed.expose(), SEED);
+        assert_eq!(seeds[0].label.as_deref(), Some("savings account"));
+        assert_eq!(seeds[1].seed.expose(), "ABC".to_owned() + &"9".repeat(78));
+        assert_eq!(seeds[1].label, None);
     }
 }
@@ -0,0 +1,58 @@
+use crate::account::ChrysalisAccount;
+use crypto::keys::pbkdf::PBKDF2_HMAC_SHA512;
+use iota_stronghold::{Location, ProcResult, Procedure, RecordHint, ResultMessage, Stronghold};
+use riker::actors::ActorSystem;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+// The snapshot layout and the key derivation follow wallet.rs, so that the snapshot can be
+// imported by wallets based on it, e.g. Firefly.
+const CLIENT_PATH: &[u8] = b"iota_seed";
+const SECRET_VAULT_PATH: &[u8] = b"iota-wallet-secret";
+const SEED_RECORD_PATH: &[u8] = b"iota-wallet-seed";
+const KEY_SALT: &[u8] = b"wallet.rs";
+const KEY_ITERATIONS: usize = 100;
+
+/// Export the seed of [account] into a new password-protected Stronghold snapshot at [path]. Only
+/// the seed is exported; wallets discover the accounts and addresses the funds are migrated to.
+pub async fn export(
+    account: &ChrysalisAccount,
+    passphrase: &str,
+    password: &str,
+    path: &Path,
+) -> Result<(), String> {
+    if path.exists() {
+        return Err("refusing to overwrite an existing file".to_owned());
+    }
+
+    let system = ActorSystem::new().map_err(|e| e.to_string())?;
+    let mut stronghold = Stronghold::init_stronghold_system(system, CLIENT_PATH.to_vec(), vec![]);
+
+    // Let Stronghold derive the seed from the mnemonic by itself, so that the seed stays inside
+    // the vault and matches what wallet.rs would have derived.
+    match stronghold
+        .runtime_exec(Procedure::BIP39Recover {
+            mnemonic: account.mnemonic().to_owned(),
+            passphrase: Some(passphrase.to_owned()),
+            output: Location::generic(SECRET_VAULT_PATH, SEED_RECORD_PATH),
+            hint: RecordHint::new(b"seed").unwrap(),
+        })
+        .await
+    {
+        ProcResult::BIP39Recover(ResultMessage::Ok(_)) => {}
+        ProcResult::BIP39Recover(ResultMessage::Error(e)) => return Err(e),
+        result => return Err(format!("unexpected result: {:?}", result)),
+    }
+
+    let mut key = Zeroizing::new([0u8; 64]);
+    PBKDF2_HMAC_SHA512(password.as_bytes(), KEY_SALT, KEY_ITERATIONS, &mut *key);
+    let snapshot_key = Zeroizing::new(key[..32].to_vec());
+
+    match stronghold
+        .write_all_to_snapshot(&snapshot_key, None, Some(path.to_path_buf()))
+        .await
+    {
+        ResultMessage::Ok(_) => Ok(()),
+        ResultMessage::Error(e) => Err(e),
+    }
+}
@@ -1,124 +1,124 @@
-use crate::account::ChrysalisAccount;
-use crate::addrs::{AddrInfo, Addrs};
+use crate::addrs::Addrs;
 use crate::args::Args;
-use iota_client::api::GetAddressesBuilder;
-use iota_legacy::client::builder::ClientBuilder as LegacyClientBuilder;
+use crate::pow::{self, PowProvider};
+use crate::seeds::SeedInfo;
+use crate::throttle::Throttle;
+use futures::future::join_all;
+use iota_client::bee_message::address::Ed25519Address;
 use iota_legacy::client::migration;
 use iota_legacy::client::migration::encode_migration_address;
 use iota_legacy::client::response::InputData;
 use iota_legacy::client::AddressInput;
-#[allow(deprecated)]
-use iota_legacy::crypto::hashes::ternary::kerl::Kerl;
+use iota_legacy::client::Client as LegacyClient;
 use iota_legacy::crypto::keys::ternary::seed::Seed;
-#[allow(deprecated)]
-use iota_legacy::crypto::keys::ternary::wots::sponge::WotsSpongePrivateKeyGeneratorBuilder;
-#[allow(deprecated)]
-use iota_legacy::crypto::keys::ternary::wots::WotsSecurityLevel;
-use iota_legacy::crypto::keys::ternary::PrivateKeyGenerator;
-use iota_legacy::crypto::signatures::ternary::PrivateKey;
-use iota_legacy::crypto::signatures::ternary::PublicKey;
 use iota_legacy::ternary::{T1B1Buf, T3B1Buf};
 use iota_legacy::ternary::{TritBuf, TryteBuf};
 use iota_legacy::transaction::bundled::{Address, BundledTransaction, BundledTransactionField};
 use log::*;
-use rayon::prelude::*;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Run [futures] concurrently if [parallel], otherwise one after another. The outputs are in the
+/// same order as [futures] either way.
+async fn run_all<F: Future>(futures: Vec<F>, parallel: bool) -> Vec<F::Output> {
+    if parallel {
+        return join_all(futures).await;
+    }
 
-#[allow(deprecated)]
-fn verify_address(seed: String, addr: AddrInfo, security_level: u8) -> Result<AddrInfo, ()> {
-    let seed_ternary: Seed = seed.parse().unwrap();
+    let mut outputs = Vec::with_capacity(futures.len());
+    for future in futures {
+        outputs.push(future.await);
+    }
 
-    let security_level_wot = match security_level {
-        1 => WotsSecurityLevel::Low,
-        2 => WotsSecurityLevel::Medium,
-        3 => WotsSecurityLevel::High,
-        _ => unreachable!(), // this should have been checked during CLI parsing
-    };
+    outputs
+}
 
-    let addr_trits = TryteBuf::try_from_str(&addr.addr)
-        .unwrap() // we've validated it during file parsing
-        .as_trits()
-        .encode::<T1B1Buf>();
-
-    let generated_addr = WotsSpongePrivateKeyGeneratorBuilder::<Kerl>::default()
-        .with_security_level(security_level_wot)
-        .build()
-        .unwrap()
-        .generate_from_seed(&seed_ternary, addr.idx)
-        .unwrap()
-        .generate_public_key()
-        .unwrap()
-        .as_trits()
-        .to_owned();
-
-    if addr_trits != generated_addr {
-        Err(())
-    } else {
-        Ok(addr)
-    }
+/// Attach [bundle] on top of tips selected by the legacy node, with the proof of work done by
+/// [pow], then store and broadcast it. Returns the attached transactions.
+async fn send_bundle(
+    client: &LegacyClient,
+    throttle: &Throttle,
+    pow: &PowProvider,
+    bundle: &[BundledTransaction],
+    min_weight_magnitude: u8,
+    depth: u8,
+) -> Result<Vec<BundledTransaction>, String> {
+    let tips = throttle
+        .run("select tips", || {
+            client.get_transactions_to_approve().depth(depth).send()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let attached = pow
+        .attach(
+            throttle,
+            bundle,
+            &tips.trunk_transaction,
+            &tips.branch_transaction,
+            min_weight_magnitude,
+        )
+        .await?;
+
+    // Never broadcast transactions that the network would reject, or that aren't the bundle
+    pow::check_attached(
+        bundle,
+        &attached,
+        &tips.trunk_transaction,
+        &tips.branch_transaction,
+        min_weight_magnitude,
+    )
+    .map_err(|e| format!("invalid attached bundle: {}", e))?;
+
+    throttle
+        .run("broadcast a migration bundle", || {
+            client.store_and_broadcast(&attached)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(attached)
 }
 
-pub fn search_and_migrate(
+pub async fn search_and_migrate(
     _args: Args,
-    _account: ChrysalisAccount,
-    _seed: String,
+    _legacy_client: LegacyClient,
+    _throttle: Arc<Throttle>,
+    _pow: PowProvider,
+    _chrysalis_addr: Ed25519Address,
+    _hrp: String,
+    _seed: SeedInfo,
 ) -> Result<(), ()> {
     error!("search and migrate - not implemented!");
     Err(())
 }
 
-pub fn collect_and_migrate(
+pub async fn collect_and_migrate(
     args: Args,
-    account: ChrysalisAccount,
-    seed: String,
+    legacy_client: LegacyClient,
+    throttle: Arc<Throttle>,
+    pow: PowProvider,
+    chrysalis_addr: Ed25519Address,
+    hrp: String,
+    seed: SeedInfo,
     addrs: Addrs,
 ) -> Result<(), ()> {
-    // Tokio asynchronous runtime to wait for asynchronous code in our synchronous code.
-    let async_rt = tokio::runtime::Runtime::new().unwrap();
+    // Seeds are only shown by their names, unless explicitly asked otherwise
+    let name = seed.name(args.unsafe_print_seeds);
+    let seed = seed.seed;
 
-    debug!("seed {}: performing address matches", seed);
-
-    // The task to run below, regardless of parallelism
-    let addr_match = |addr: &AddrInfo| {
-        if let Ok(matched) = verify_address(seed.clone(), addr.clone(), args.security_level) {
-            debug!("seed {}: accept matching address {}", seed, addr.addr);
-            Some(matched)
-        } else {
-            warn!("seed {}: reject mismatched address {}", seed, addr.addr);
-            None
-        }
-    };
-
-    // Match addresses with the given seed. If multiple seeds are in a file and multiple addresses
-    // are in a file, then there must be addresses that don't belong to some other seeds. This
-    // process filters out any address that doesn't belong to [seed], as this task is only for one
-    // seed. [addrs] is moved into and shadowed.
-    let addrs: Vec<AddrInfo> = if args.parallel_mode.is_parallel_search() {
-        addrs.par_iter().filter_map(addr_match).collect()
-    } else {
-        addrs.iter().filter_map(addr_match).collect()
-    };
-
-    // If all addresses are filtered out, exit early
+    // If no address belongs to this seed, exit early
     if addrs.is_empty() {
-        warn!("seed {}: no matching address is accepted! exiting.", seed);
-        eprintln!("> seed {}: no matching address is accepted! exiting.", seed);
+        warn!("seed {}: no matching address is accepted! exiting.", name);
+        eprintln!("> seed {}: no matching address is accepted! exiting.", name);
         return Err(());
     }
 
-    // This instance from an older version of iota-client connects to the legacy network.
-    let mut legacy_client = LegacyClientBuilder::new()
-        .node(&args.legacy_node)
-        .unwrap()
-        .permanode(&args.permanode)
-        .unwrap()
-        .quorum(true)
-        .build()
-        .unwrap();
-
     // This prepared version of address information input is unfortunately required by the legacy
     // client.
     let addrs_prep: Vec<AddressInput> = addrs
-        .into_iter()
+        .iter()
         .map(|addr| {
             AddressInput {
                 address: Address::try_from_inner(
@@ -140,14 +140,22 @@ pub fn collect_and_migrate(
     // 2. Input for next steps (the legacy client require exactly this piece of information)
     debug!(
         "seed {}: connecting to the legacy IOTA network to check address information...",
-        seed
-    );
-    let addrs_queried_results = async_rt.block_on(
-        legacy_client
-            .get_ledger_account_data_for_migration()
-            .with_addresses(addrs_prep)
-            .finish(),
+        name
     );
+    let addrs_queried_results = throttle
+        .run("fetch address information", || {
+            let mut legacy_client = legacy_client.clone();
+            let addrs_prep = addrs_prep.clone();
+
+            async move {
+                legacy_client
+                    .get_ledger_account_data_for_migration()
+                    .with_addresses(addrs_prep)
+                    .finish()
+                    .await
+            }
+        })
+        .await;
 
     // Exit early if there is any error. The resulting tuple is destructed then.
     let (balance, input_data, any_spent) = match addrs_queried_results {
@@ -155,11 +163,11 @@ pub fn collect_and_migrate(
         Err(err) => {
             error!(
                 "seed {}: failed to fetch address information: {}",
-                seed, err
+                name, err
             );
             eprintln!(
                 "> seed {}: failed to fetch address information: {}",
-                seed, err
+                name, err
             );
             return Err(());
         }
@@ -167,7 +175,7 @@ pub fn collect_and_migrate(
 
     info!(
         "seed {}: queried total balance {}, {} inputs, {}",
-        seed,
+        name,
         balance,
         input_data.len(),
         if any_spent {
@@ -179,14 +187,14 @@ pub fn collect_and_migrate(
 
     // If there isn't any input data, then there's nothing we can do. Exit early.
     if balance == 0 || input_data.is_empty() {
-        warn!("seed {}: nothing can be migrated! exiting", seed);
-        eprintln!("> seed {}: nothing can be migrated! exiting", seed);
+        warn!("seed {}: nothing can be migrated! exiting", name);
+        eprintln!("> seed {}: nothing can be migrated! exiting", name);
         return Err(());
     }
 
     // Filter out spent addresses, if there is any.
     let mut input_data: Vec<_> = if any_spent {
-        debug!("seed {}: filtering out already spent addresses", seed);
+        debug!("seed {}: filtering out already spent addresses", name);
 
         input_data
             .into_iter()
@@ -194,7 +202,7 @@ pub fn collect_and_migrate(
                 if data.spent {
                     warn!(
                         "seed {}: address {} has been spent, dropping",
-                        seed,
+                        name,
                         migration::add_tryte_checksum(data.address.clone()).unwrap()
                     );
                 }
@@ -208,14 +216,14 @@ pub fn collect_and_migrate(
 
     // If there isn't any input data, then there's nothing we can do. Exit early.
     if input_data.is_empty() {
-        warn!("seed {}: nothing can be migrated! exiting", seed);
-        eprintln!("> seed {}: nothing can be migrated! exiting", seed);
+        warn!("seed {}: nothing can be migrated! exiting", name);
+        eprintln!("> seed {}: nothing can be migrated! exiting", name);
         return Err(());
     }
 
     // Sort addresses by their balances to ensure that addresses with small balances get bundled
     // together to try avoiding dust inputs.
-    debug!("seed {}: sorting addresses by balances", seed);
+    debug!("seed {}: sorting addresses by balances", name);
     input_data.sort_unstable_by_key(|data| data.balance);
 
     // Bundle address, with every bundle containing at least 1 Mi to go over the dust allowance.
@@ -223,7 +231,7 @@ pub fn collect_and_migrate(
     // XXX: the last bundle doesn't necessarily go beyond the dust allowance
     debug!(
         "seed {}: partitioning addresses into at-least-1-Mi bundles",
-        seed
+        name
     );
     let bundles: Vec<Vec<InputData>> =
         input_data
@@ -256,10 +264,10 @@ pub fn collect_and_migrate(
                 new_acc
             });
 
-    debug!("seed {}: created {} bundles", seed, bundles.len());
+    debug!("seed {}: created {} bundles", name, bundles.len());
 
     // Bundles that are still dusts need to be filtered out, sorry.
-    debug!("seed {}: checking for dusts", seed);
+    debug!("seed {}: checking for dusts", name);
     let (bundles, bundles_dust): (Vec<Vec<InputData>>, Vec<Vec<InputData>>) =
         bundles.into_iter().partition(|bundle| {
             let bundle_balance: u64 = bundle.iter().map(|data| data.balance).sum();
@@ -273,74 +281,79 @@ pub fn collect_and_migrate(
             warn!(
                 "seed {}: this bundle contains < 1 Mi balance, which is considered as a dust input\
                 , and will not be migrated: {:?}",
-                seed, bundle_dust_summary
+                name, bundle_dust_summary
             );
         }
     } else {
-        debug!("seed {}: no dust bundle is found", seed);
+        debug!("seed {}: no dust bundle is found", name);
     }
 
     // If all bundles are dust (filtered out), exit early
     if bundles.is_empty() {
         warn!(
             "seed {}: no bundle can be migrated due to dust prevention! exiting.",
-            seed
+            name
         );
         eprintln!(
             "> seed {}: no bundle can be migrated due to dust prevention! exiting.",
-            seed
+            name
         );
         return Err(());
     }
 
-    // Generate the target address on Chrysalis.
-    debug!("seed {}: generating target Chrysalis address...", seed);
-    let chrysalis_addr = {
-        let generated_addrs = async_rt.block_on(
-            // This is a different [Seed]!
-            GetAddressesBuilder::new(&iota_client::Seed::from_bytes(account.seed()))
-                .with_account_index(args.target_account)
-                .with_range(args.target_address..args.target_address + 1)
-                .get_all_raw(),
-        );
-
-        let iota_client::bee_message::address::Address::Ed25519(address) =
-            generated_addrs.unwrap()[0].0;
-
-        address
-    };
-
     // Create (prepare) migration bundles using the migration facilities in the legacy client, then
     // sign on them.
-    debug!("seed {}: preparing and signing migration bundles...", seed);
-    let bundles_signed: Vec<_> = bundles
-        .iter()
-        .map(|bundle| {
-            async_rt.block_on(migration::create_migration_bundle(
-                &legacy_client,
-                chrysalis_addr,
-                bundle.clone(),
-            ))
-        })
-        .filter_map(|result| {
+    debug!("seed {}: preparing and signing migration bundles...", name);
+    let mut bundles_prepared = Vec::with_capacity(bundles.len());
+    for bundle in bundles.iter() {
+        bundles_prepared.push(
+            throttle
+                .run("prepare a migration bundle", || {
+                    migration::create_migration_bundle(
+                        &legacy_client,
+                        chrysalis_addr,
+                        bundle.clone(),
+                    )
+                })
+                .await,
+        );
+    }
+
+    // Each prepared bundle is kept with its inputs, which are needed to sign on it
+    let bundles_prepared: Vec<_> = bundles_prepared
+        .into_iter()
+        .zip(bundles.iter())
+        .filter_map(|(result, input_data)| {
             match result {
-                Ok(bundle) => Some(bundle),
+                Ok(bundle) => Some((bundle, input_data.clone())),
                 Err(err) => {
                     // FIXME: which bundle?
                     error!(
                         "seed {}: failed to create a migration bundle: {}, skipping",
-                        seed, err
+                        name, err
                     );
                     None
                 }
             }
         })
-        .zip(bundles.iter())
-        .map(|(prepared_bundle, input_data)| {
-            let ternary_seed: Seed = seed.parse().unwrap();
+        .collect();
 
-            migration::sign_migration_bundle(ternary_seed, prepared_bundle, input_data.clone())
-        })
+    // Deriving the keys to sign with would block the asynchronous runtime otherwise
+    let bundles_signed = tokio::task::spawn_blocking(move || {
+        bundles_prepared
+            .into_iter()
+            .map(|(prepared_bundle, input_data)| {
+                let ternary_seed: Seed = seed.expose().parse().unwrap();
+
+                migration::sign_migration_bundle(ternary_seed, prepared_bundle, input_data)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap();
+
+    let bundles_signed: Vec<_> = bundles_signed
+        .into_iter()
         .filter_map(|result| {
             match result {
                 Ok(bundle) => Some(bundle),
@@ -348,7 +361,7 @@ pub fn collect_and_migrate(
                     // FIXME: which bundle?
                     error!(
                         "seed {}: failed to sign on a migration bundle: {}, skipping",
-                        seed, err
+                        name, err
                     );
                     None
                 }
@@ -356,28 +369,27 @@ pub fn collect_and_migrate(
         })
         .collect();
 
-    debug!("seed {}: signed {} bundles", seed, bundles_signed.len());
+    debug!("seed {}: signed {} bundles", name, bundles_signed.len());
 
     // Send the migration bundles to the legacy network.
-    debug!("seed {}: sending bundles", seed);
+    debug!("seed {}: sending bundles", name);
     let bundles_sent = if args.dry_run {
         info!(
             "seed {}: dry-run - pretending that the bundles have been sent successfully",
-            seed
+            name
         );
-        eprintln!("> seed {}: dry-run finished", seed);
+        eprintln!("> seed {}: dry-run finished", name);
 
         None
     } else {
         let f_send = |bundle: &Vec<_>| {
-            async_rt.block_on(
-                legacy_client
-                    .send_trytes()
-                    .with_trytes(bundle.clone())
-                    .with_depth(2)
-                    .with_local_pow(true)
-                    .with_min_weight_magnitude(args.minimum_weight_magnitude)
-                    .finish(),
+            send_bundle(
+                &legacy_client,
+                &throttle,
+                &pow,
+                bundle,
+                args.minimum_weight_magnitude,
+                args.depth,
             )
         };
 
@@ -388,31 +400,26 @@ pub fn collect_and_migrate(
                     // FIXME: which bundle?
                     error!(
                         "seed {}: failed to send a migration bundle: {}, dropping",
-                        seed, err
+                        name, err
                     );
                     None
                 }
             }
         };
 
-        let bundles_sent: Vec<_> = if args.parallel_mode.is_parallel_search() {
-            bundles_signed
-                .par_iter()
-                .map(f_send)
-                .filter_map(f_filter)
-                .collect()
-        } else {
-            bundles_signed
-                .iter()
-                .map(f_send)
-                .filter_map(f_filter)
-                .collect()
-        };
+        let bundles_sent: Vec<_> = run_all(
+            bundles_signed.iter().map(f_send).collect(),
+            args.parallel_mode.is_parallel_search(),
+        )
+        .await
+        .into_iter()
+        .filter_map(f_filter)
+        .collect();
 
-        debug!("seed {}: sent {} bundles", seed, bundles_sent.len());
+        debug!("seed {}: sent {} bundles", name, bundles_sent.len());
         eprintln!(
             "> seed {}: sent {} bundles, waiting for confirmation...",
-            seed,
+            name,
             bundles_sent.len()
         );
 
@@ -421,21 +428,21 @@ pub fn collect_and_migrate(
 
     // Wait until the messages get confirmed. If not, we reattach them.
     if let Some(ref bundles_sent) = bundles_sent {
-        debug!("seed {}: waiting for the confirmation of bundles...", seed);
+        debug!("seed {}: waiting for the confirmation of bundles...", name);
 
         // Keep the starting time. If any bundle is not confirmed after 3 minutes, we perform a
         // reattachment.
-        let mut time = std::time::Instant::now();
-
-        // Tasks to run regardless of parallelism
-        let f_partbndl = |txs: &&Vec<BundledTransaction>| {
-            let bundle_hash = txs.first().unwrap().bundle();
-            let response = async_rt.block_on(
-                legacy_client
-                    .find_transactions()
-                    .bundles(&[*bundle_hash])
-                    .send(),
-            );
+        let mut time = Instant::now();
+
+        // Tasks to run regardless of parallelism. The futures only hold references.
+        let (client, name, throttle) = (&legacy_client, &name, &*throttle);
+        let f_confirmed = |txs: &Vec<BundledTransaction>| async move {
+            let bundle_hashes = [*txs.first().unwrap().bundle()];
+            let response = throttle
+                .run("find a migration bundle", || {
+                    client.find_transactions().bundles(&bundle_hashes).send()
+                })
+                .await;
 
             match response {
                 Ok(response) => {
@@ -445,14 +452,19 @@ pub fn collect_and_migrate(
                         .iter_trytes()
                         .map(char::from)
                         .collect::<String>();
-                    let response = async_rt.block_on(legacy_client.is_confirmed(&[tx_hash]));
+                    let tx_hashes = [tx_hash];
+                    let response = throttle
+                        .run("query the confirmation status", || {
+                            client.is_confirmed(&tx_hashes)
+                        })
+                        .await;
 
                     match response {
                         Ok(is_confirmed) => is_confirmed[0],
                         Err(error) => {
                             warn!(
                                 "seed {}: failed to query confirmation status for bundle {}: {}",
-                                seed, tx_hash_str, error
+                                name, tx_hash_str, error
                             );
                             false // treat it as unconfirmed
                         }
@@ -462,7 +474,7 @@ pub fn collect_and_migrate(
                     // FIXME: which bundle? We don't know the tx hash yet here!
                     warn!(
                         "seed {}: failed to query confirmation status: {}",
-                        seed, error
+                        name, error
                     );
                     false
                 }
@@ -472,75 +484,62 @@ pub fn collect_and_migrate(
         // Loop until all are confirmed. XXX: what if there are some never get confirmed?
         loop {
             // Wait for 10 seconds before and during checks
-            std::thread::sleep(std::time::Duration::from_secs(10));
-
-            // We don't actually need to look at confirmed bundles any more; using partition() is
-            // just for easier filtering.
-            debug!("seed {}: checking for confirmation statuses...", seed);
-            let (_, bundles_unconfirmed): (Vec<_>, Vec<_>) =
-                if args.parallel_mode.is_parallel_search() {
-                    bundles_sent.par_iter().partition(f_partbndl)
-                } else {
-                    bundles_sent.iter().partition(f_partbndl)
-                };
+            tokio::time::sleep(Duration::from_secs(10)).await;
+
+            // We don't actually need to look at confirmed bundles any more
+            debug!("seed {}: checking for confirmation statuses...", name);
+            let confirmed = run_all(
+                bundles_sent.iter().map(f_confirmed).collect(),
+                args.parallel_mode.is_parallel_search(),
+            )
+            .await;
+            let bundles_unconfirmed: Vec<_> = bundles_sent
+                .iter()
+                .zip(confirmed)
+                .filter_map(|(bundle, confirmed)| if confirmed { None } else { Some(bundle) })
+                .collect();
 
             if bundles_unconfirmed.is_empty() {
-                debug!("seed {}: all bundles confirmed, continue", seed);
+                debug!("seed {}: all bundles confirmed, continue", name);
                 break;
             }
 
             // Otherwise, we check if it has been 3 minutes. If so, we reattch all unconfirmed
             // bundles; otherwise, just continue the loop.
             if time.elapsed().as_secs() > 180 {
-                debug!("seed {}: unconfirmed bundles will be reattached", seed);
-
-                bundles_unconfirmed
-                    .iter()
-                    .map(|bundle| {
-                        let hash = bundle.first().unwrap().bundle();
-                        let hash_str = hash
-                            .encode::<T3B1Buf>()
-                            .iter_trytes()
-                            .map(char::from)
-                            .collect::<String>();
-
-                        debug!("seed {}: reattaching bundle {}", seed, hash_str);
-
-                        // Why we have an async fn here?
-                        let builder = async_rt.block_on(legacy_client.reattach(hash));
-
-                        async_rt.block_on(
-                            builder
-                                .unwrap() // XXX: what would happen here?
-                                .with_depth(2)
-                                .with_min_weight_magnitude(args.minimum_weight_magnitude)
-                                .with_local_pow(true)
-                                .finish(),
-                        )
-                    })
-                    .for_each(|result| {
-                        match result {
-                            Ok(vec_tx) => {
-                                let hash_str = vec_tx
-                                    .first()
-                                    .unwrap()
-                                    .bundle()
-                                    .encode::<T3B1Buf>()
-                                    .iter_trytes()
-                                    .map(char::from)
-                                    .collect::<String>();
-
-                                debug!("seed {}: reattached bundle {}", seed, hash_str);
-                            }
-                            Err(err) => {
-                                // XXX: which bundle?
-                                warn!("seed {}: failed to reattach a bundle: {}", seed, err);
-                            }
-                        }
-                    });
+                debug!("seed {}: unconfirmed bundles will be reattached", name);
+
+                for bundle in bundles_unconfirmed {
+                    let hash = bundle.first().unwrap().bundle();
+                    let hash_str = hash
+                        .encode::<T3B1Buf>()
+                        .iter_trytes()
+                        .map(char::from)
+                        .collect::<String>();
+
+                    debug!("seed {}: reattaching bundle {}", name, hash_str);
+
+                    let result = send_bundle(
+                        client,
+                        throttle,
+                        &pow,
+                        bundle,
+                        args.minimum_weight_magnitude,
+                        args.depth,
+                    )
+                    .await;
+
+                    match result {
+                        Ok(_) => debug!("seed {}: reattached bundle {}", name, hash_str),
+                        Err(err) => warn!(
+                            "seed {}: failed to reattach bundle {}: {}",
+                            name, hash_str, err
+                        ),
+                    }
+                }
 
                 // Update the timer
-                time = std::time::Instant::now();
+                time = Instant::now();
             }
         }
     }
@@ -563,7 +562,7 @@ pub fn collect_and_migrate(
     let total_amount: u64 = input_data.iter().map(|data| data.balance).sum();
     let to_addr_ternary =
         migration::add_tryte_checksum(encode_migration_address(chrysalis_addr).unwrap()).unwrap();
-    let to_addr_bech32 = migration::Address::Ed25519(chrysalis_addr).to_bech32("iota");
+    let to_addr_bech32 = migration::Address::Ed25519(chrysalis_addr).to_bech32(&hrp);
     let bundles_str: String = if let Some(ref bundles_sent) = bundles_sent {
         bundles_sent
             .iter()
@@ -623,7 +622,7 @@ pub fn collect_and_migrate(
             Transaction bundle hash(es):{}\n\
             Bundle trytes:{}\n\
             ========================",
-        seed,
+        name,
         from_addrs_info,
         to_addr_ternary,
         to_addr_bech32,
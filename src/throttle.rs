@@ -0,0 +1,188 @@
+use iota_legacy::client::Error as LegacyError;
+use log::*;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+
+/// How many times a request is retried before giving up.
+const MAX_RETRIES: u32 = 5;
+
+/// The delay before the first retry, which is doubled on every retry.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest delay between two retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A token bucket holding at most one second worth of requests.
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Limits the requests sent to the legacy node by all seeds together: how many can be in flight
+/// at once, and how many can be started per second. Requests rejected by the node for being too
+/// many (HTTP 429) or failing on the node side (HTTP 5xx) are retried with exponential backoff.
+///
+/// A "request" here is one operation of the legacy client, which is all we can wrap. Some of them
+/// send several HTTP requests one after another (e.g. fetching address information, or storing
+/// then broadcasting a bundle), but count once against both limits.
+pub struct Throttle {
+    in_flight: Semaphore,
+    requests_per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Throttle {
+    pub fn new(max_in_flight: usize, requests_per_second: f64) -> Self {
+        Self {
+            in_flight: Semaphore::new(max_in_flight),
+            requests_per_second,
+            bucket: Mutex::new(Bucket {
+                tokens: requests_per_second,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a request can be started within the requests-per-second budget.
+    async fn take_token(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second)
+                    .min(self.requests_per_second.max(1.0));
+                bucket.last = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                (1.0 - bucket.tokens) / self.requests_per_second
+            };
+
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+
+    /// Run the operation made by [request] within the limits, retrying it if the node asks us to.
+    /// [what] describes the request in logs.
+    pub async fn run<T, F, Fut>(&self, what: &str, mut request: F) -> Result<T, LegacyError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, LegacyError>>,
+    {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut retries = 0;
+
+        loop {
+            let result = {
+                let _permit = self.in_flight.acquire().await.unwrap();
+                self.take_token().await;
+
+                request().await
+            };
+
+            match result {
+                Err(err) if is_retryable(&err) && retries < MAX_RETRIES => {
+                    retries += 1;
+                    warn!(
+                        "failed to {}: {}, retrying in {} s ({}/{})",
+                        what,
+                        err,
+                        backoff.as_secs(),
+                        retries,
+                        MAX_RETRIES
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether the node may accept the request if it's sent again later.
+fn is_retryable(err: &LegacyError) -> bool {
+    match err {
+        LegacyError::ResponseError(status, _) => *status == 429 || (500..600).contains(status),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Run a request that fails with [status] [failures] times before succeeding, and return its
+    /// result and how many times it's sent.
+    async fn run_failing(status: u16, failures: u32) -> (Result<(), LegacyError>, u32) {
+        let throttle = Throttle::new(1, 10.0);
+        let sent = AtomicU32::new(0);
+
+        let result = throttle
+            .run("test", || {
+                let n = sent.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if n < failures {
+                        Err(LegacyError::ResponseError(status, "test".to_owned()))
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await;
+
+        (result, sent.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_with_backoff() {
+        let start = Instant::now();
+        let (result, sent) = run_failing(429, 3).await;
+
+        assert!(result.is_ok());
+        assert_eq!(sent, 4);
+        // 1 + 2 + 4 seconds
+        assert!(start.elapsed() >= Duration::from_secs(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_retries() {
+        let (result, sent) = run_failing(503, u32::MAX).await;
+
+        assert!(result.is_err());
+        assert_eq!(sent, MAX_RETRIES + 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_retries_on_client_errors() {
+        let (result, sent) = run_failing(400, u32::MAX).await;
+
+        assert!(result.is_err());
+        assert_eq!(sent, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_per_second() {
+        let throttle = Throttle::new(8, 2.0);
+        let start = Instant::now();
+
+        for _ in 0..6 {
+            throttle
+                .run("test", || async { Ok::<_, LegacyError>(()) })
+                .await
+                .unwrap();
+        }
+
+        // 2 at once from the full bucket, then 2 per second
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    }
+}